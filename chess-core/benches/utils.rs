@@ -102,10 +102,10 @@ fn utils_benchmark(c: &mut Criterion) {
         b.iter(|| black_box(Board::default()).obstruct(chess_engine::Color::White))
     });
     c.bench_function("just mark hide", |b| {
-        b.iter(|| black_box(Board::default()).hide(chess_engine::Color::White))
+        b.iter(|| black_box(Board::default()).hide(chess_engine::Color::White, None))
     });
     c.bench_function("obstruction hide", |b| {
-        b.iter(|| black_box(Board::default()).hide_and_obstruct(chess_engine::Color::White))
+        b.iter(|| black_box(Board::default()).hide_and_obstruct(chess_engine::Color::White, None))
     });
     // c.bench_function("stupid game 100", |b| {
     //     b.iter(|| {
@@ -1,12 +1,15 @@
 #![allow(dead_code)]
-use crate::core::engine::{Board, Game, Move};
+use crate::core::engine::{Board, Color, Game, Move};
+use crate::core::eval::evaluate;
+use crate::core::search::{best_move, score_move};
 
 // Only for server-side/offline match
 pub trait Algorithm {
     fn solve(&self, game: &Game) -> Move;
 }
 
-pub type EvaluationFunc = dyn Fn(&Board) -> i32;
+/** Scores board from the point of view of given player */
+pub type EvaluationFunc = dyn Fn(&Board, Color) -> i32;
 
 pub struct MinMaxBot {
     max_depth: u32,
@@ -24,6 +27,23 @@ impl MinMaxBot {
 
 impl Algorithm for MinMaxBot {
     fn solve(&self, game: &Game) -> Move {
-        todo!()
+        best_move(game, self.max_depth.min(u8::MAX as u32) as u8)
+            .expect("Bot shouldn't be asked to move in finished game")
     }
 }
+
+/** All moves with their search scores for the player to move, best first.
+ *
+ * Every root move is searched to `depth - 1` plies with material evaluation. */
+pub fn rank_moves(game: &Game, depth: u8) -> Vec<(Move, i32)> {
+    let mut ranked: Vec<_> = game
+        .legal_moves_expanded()
+        .into_iter()
+        .map(|_move| {
+            let score = score_move(game, _move.clone(), depth.saturating_sub(1), &evaluate);
+            (_move, score)
+        })
+        .collect();
+    ranked.sort_by(|(_, a), (_, b)| b.cmp(a));
+    ranked
+}
@@ -0,0 +1,50 @@
+//! Chess clock for timed games.
+//!
+//! Clock only counts time it is told about, so it works the same for
+//! local games and for a server measuring time between messages.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::engine::Color;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Clock {
+    /** Time left for Black and White */
+    remaining: [Duration; 2],
+    increment: Duration,
+}
+
+impl Clock {
+    pub fn new(initial: Duration, increment: Duration) -> Clock {
+        Clock {
+            remaining: [initial; 2],
+            increment,
+        }
+    }
+
+    pub fn remaining(&self, color: Color) -> Duration {
+        self.remaining[color as usize >> 7]
+    }
+
+    pub fn increment(&self) -> Duration {
+        self.increment
+    }
+
+    /** Takes elapsed time from the player, returns `true` if their flag fell */
+    pub fn charge(&mut self, color: Color, elapsed: Duration) -> bool {
+        let remaining = &mut self.remaining[color as usize >> 7];
+        *remaining = remaining.saturating_sub(elapsed);
+        remaining.is_zero()
+    }
+
+    /** Gives increment to the player who just moved */
+    pub fn add_increment(&mut self, color: Color) {
+        self.remaining[color as usize >> 7] += self.increment;
+    }
+
+    pub fn is_flagged(&self, color: Color) -> bool {
+        self.remaining(color).is_zero()
+    }
+}
@@ -1,4 +1,6 @@
-use crate::core::engine::{Color, PieceType};
+use serde::{Deserialize, Serialize};
+
+use crate::core::engine::{Color, GameEndState, PieceType};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Figure {
@@ -9,6 +11,26 @@ pub struct Figure {
     pub can_move: bool,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveInfo {
+    pub san: String,
+    pub is_check: bool,
+    pub is_mate: bool,
+    pub is_capture: bool,
+    /** Index of the move in the played line, starting from 0 */
+    pub ply: usize,
+}
+
+/** Everything about current position a thin web layer needs to show it */
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PositionSnapshot {
+    pub fen: String,
+    pub side_to_move: Color,
+    pub in_check: bool,
+    pub legal_uci: Vec<String>,
+    pub outcome: Option<GameEndState>,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum Cell {
     Empty,
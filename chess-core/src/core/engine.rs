@@ -1,16 +1,22 @@
 #![allow(dead_code)]
 
+use std::collections::HashMap;
 use std::fmt::Display;
+use std::sync::OnceLock;
+use std::time::Duration;
 use std::{fmt::Debug, iter::zip};
 
+use rand::{seq::SliceRandom, Rng};
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, Bytes};
 
-use crate::core::definitions::ImplicitMove;
+use crate::core::clock::Clock;
+use crate::core::definitions::{ImplicitMove, MoveInfo, PositionSnapshot};
 use crate::core::utils::{
-    between, compact_pos, distance, in_direction, is_in_diagonal_line, is_in_straight_line,
-    is_valid_coord, pos_to_str, unpack_pos,
+    between, center_distance, compact_pos, distance, in_direction, is_in_diagonal_line,
+    is_in_straight_line, is_valid_coord, line_step, pos_to_str, str_to_pos, unpack_pos,
 };
+use crate::core::zobrist;
 
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum CastlingSide {
@@ -55,6 +61,25 @@ impl CheckType {
     }
 }
 
+/** Which part of origin square SAN needs to tell the move apart */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Disambiguation {
+    None,
+    File,
+    Rank,
+    Both,
+}
+
+/** How checkmate was delivered */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatePattern {
+    /** Rook or queen mates along the back rank, king is boxed in by own pieces */
+    BackRank,
+    /** Knight mates king surrounded by own pieces */
+    Smothered,
+    Other,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Move {
     piece: Piece,
@@ -64,8 +89,8 @@ pub struct Move {
 }
 
 impl Move {
-    #[cfg(debug_assertions)]
-    pub fn new_debug(piece: Piece, move_type: MoveType, check: CheckType) -> Move {
+    /** Move as is, nothing is checked. See `try_new` for a validating version. */
+    pub fn new(piece: Piece, move_type: MoveType, check: CheckType) -> Move {
         Move {
             piece,
             move_type,
@@ -73,6 +98,80 @@ impl Move {
         }
     }
 
+    /** Move with `move_type` checked to make sense for the piece: only pawns promote,
+     * push twice and capture en passant, only kings castle, and captured piece
+     * belongs to the opponent. Legality on the board isn't checked. */
+    pub fn try_new(piece: Piece, move_type: MoveType, check: CheckType) -> Result<Move, String> {
+        let piece_type = piece.type_();
+        if !piece_type.is_valid() {
+            return Err(format!(
+                "No piece to move on {}",
+                pos_to_str(piece.position)
+            ));
+        }
+        let is_pawn = piece_type == PieceType::Pawn;
+        let last_row = match piece.color() {
+            Color::White => 0x70,
+            Color::Black => 0x00,
+        };
+        match move_type {
+            MoveType::QuietMove(_) | MoveType::Capture(_) => {}
+            MoveType::Castling(..) if piece_type != PieceType::King => {
+                return Err("Only king can castle".to_string())
+            }
+            MoveType::Castling(_, rook) if rook.type_() != PieceType::Rook => {
+                return Err("King can castle only with a rook".to_string())
+            }
+            MoveType::Castling(..) => {}
+            MoveType::PromotionQuiet(_, new_type) | MoveType::PromotionCapture(_, new_type) => {
+                if !is_pawn {
+                    return Err("Only pawn can promote".to_string());
+                }
+                if !matches!(
+                    new_type,
+                    PieceType::Knight | PieceType::Bishop | PieceType::Rook | PieceType::Queen
+                ) {
+                    return Err(format!("Pawn can't promote to {new_type:?}"));
+                }
+            }
+            MoveType::PawnDoublePush(_) if !is_pawn => {
+                return Err("Only pawn can push twice".to_string())
+            }
+            MoveType::PawnDoublePush(_) => {}
+            MoveType::EnPassantCapture(target, _)
+                if !is_pawn || target.type_() != PieceType::Pawn =>
+            {
+                return Err("En passant is pawn capturing a pawn".to_string())
+            }
+            MoveType::EnPassantCapture(..) => {}
+        }
+        if let MoveType::Capture(target)
+        | MoveType::PromotionCapture(target, _)
+        | MoveType::EnPassantCapture(target, _) = move_type
+        {
+            if !target.type_().is_valid() || target.color() == piece.color() {
+                return Err(format!(
+                    "Nothing to capture on {}",
+                    pos_to_str(target.position)
+                ));
+            }
+        }
+        let _move = Move::new(piece, move_type, check);
+        let is_promotion = matches!(
+            move_type,
+            MoveType::PromotionQuiet(..) | MoveType::PromotionCapture(..)
+        );
+        if is_pawn && (_move.end_position() & 0xf0 == last_row) != is_promotion {
+            return Err("Pawn promotes exactly on the last rank".to_string());
+        }
+        Ok(_move)
+    }
+
+    #[cfg(debug_assertions)]
+    pub fn new_debug(piece: Piece, move_type: MoveType, check: CheckType) -> Move {
+        Move::new(piece, move_type, check)
+    }
+
     pub fn piece(&self) -> &Piece {
         &self.piece
     }
@@ -95,6 +194,19 @@ impl Move {
         }
     }
 
+    /** Destination written in UCI, castling from non-classical squares of Chess960
+     * is written as king taking own rook to tell it from king steps */
+    fn uci_end_position(&self) -> u8 {
+        match &self.move_type {
+            MoveType::Castling(side, rook)
+                if self.piece.position & 0x0f != 0x04 || rook.position & 0x0f != *side as u8 =>
+            {
+                rook.position
+            }
+            _ => self.end_position(),
+        }
+    }
+
     fn castling_rook_position(&self) -> u8 {
         match &self.move_type {
             MoveType::Castling(side, _) => {
@@ -115,17 +227,41 @@ impl Move {
     pub fn check(&self) -> CheckType {
         self.check
     }
-}
 
-impl Display for Move {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
+    /** Move in UCI long algebraic notation, like "e2e4" or "e7e8q" */
+    pub fn to_uci(&self) -> String {
+        let mut uci = format!(
             "{}{}",
             pos_to_str(self.piece.position),
-            pos_to_str(self.end_position())
+            pos_to_str(self.uci_end_position())
+        );
+        if let MoveType::PromotionQuiet(_, new_type) | MoveType::PromotionCapture(_, new_type) =
+            self.move_type
+        {
+            if new_type.is_valid() {
+                uci.push(new_type.letter());
+            }
+        }
+        uci
+    }
+
+    pub fn is_capture(&self) -> bool {
+        matches!(
+            self.move_type,
+            MoveType::Capture(_) | MoveType::PromotionCapture(..) | MoveType::EnPassantCapture(..)
         )
     }
+
+    /** Synthetic moves made up by `from_fen` to carry check or en passant state */
+    fn is_synthetic(&self) -> bool {
+        self.piece.position == self.end_position()
+    }
+}
+
+impl Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_uci())
+    }
 }
 
 impl ImplicitMove for Move {
@@ -145,9 +281,12 @@ impl ImplicitMove for Move {
     }
 }
 
-/** Variation of 0x88 board */
+/** Variation of 0x88 board
+ *
+ * Boards are equal when their bytes are, so moved, castling and unknown cell
+ * flags count too: same placement reached differently may compare unequal. */
 #[serde_as]
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Board {
     #[serde_as(as = "Bytes")]
     arr: [u8; 128],
@@ -403,41 +542,99 @@ impl Board {
             .iter_pieces()
             .filter(|attacker| {
                 attacker.color() != target_king.color()
-                    && match attacker.type_() {
-                        PieceType::Bishop => {
-                            is_in_diagonal_line(attacker.position, target_king.position)
-                        }
-                        PieceType::Rook => {
-                            is_in_straight_line(attacker.position, target_king.position)
-                        }
-                        PieceType::Queen => {
-                            is_in_diagonal_line(attacker.position, target_king.position)
-                                || is_in_straight_line(attacker.position, target_king.position)
-                        }
-                        // No one else cannot pin another piece
-                        _ => false,
-                    }
+                    && attacker.slides_towards(target_king.position)
             })
             .filter_map(|attacker| {
-                let mut pinned_piece = None;
-                for pos in between(attacker.position, target_king.position) {
-                    let code = self.arr[pos as usize];
-                    if code != 0x00 {
-                        if pinned_piece.is_none() {
-                            let piece = Piece::from_code(code, pos);
-                            pinned_piece = Some(piece);
-                        } else {
-                            pinned_piece = None;
-                            break;
-                        }
-                    }
-                }
-                pinned_piece.zip(Some(attacker))
+                self.sole_blocker(attacker.position, target_king.position)
+                    .zip(Some(attacker))
             })
             .collect();
         pinned_pieces
     }
 
+    /** Pairs of pinned piece and its pinner, pins are relative to the given king.
+     *
+     * With position of the pawn which can be captured en passant, pawn that can't
+     * capture it because both pawns would leave king's rank at once is reported too. */
+    pub fn pinned_pieces(&self, king: Piece, enpassant: Option<u8>) -> Vec<(Piece, Piece)> {
+        let mut pinned_pieces = self.count_pinned_pieces(king);
+        // Lone enemy piece in between shields the king, but isn't pinned
+        pinned_pieces.retain(|(pinned, _)| pinned.color() == king.color());
+        let Some(pawn_pos) = enpassant else {
+            return pinned_pieces;
+        };
+        for attacker in self.iter_pieces().filter(|attacker| {
+            attacker.color() != king.color()
+                && matches!(attacker.type_(), PieceType::Rook | PieceType::Queen)
+                && attacker.position & 0xf0 == king.position & 0xf0
+                && pawn_pos & 0xf0 == king.position & 0xf0
+        }) {
+            let blockers: Vec<_> = between(attacker.position, king.position)
+                .filter(|pos| self.arr[*pos as usize] != 0x00)
+                .map(|pos| Piece::from_code(self.arr[pos as usize], pos))
+                .collect();
+            if let [first, second] = blockers[..] {
+                let capturer = if first.position == pawn_pos {
+                    second
+                } else if second.position == pawn_pos {
+                    first
+                } else {
+                    continue;
+                };
+                if capturer.color() == king.color()
+                    && capturer.type_() == PieceType::Pawn
+                    && capturer.position.abs_diff(pawn_pos) == 0x01
+                {
+                    pinned_pieces.push((capturer, attacker));
+                }
+            }
+        }
+        pinned_pieces
+    }
+
+    /** Piece pinning the piece on the position to its own king, if there is one */
+    pub fn is_pinned(&self, pos: u8) -> Option<Piece> {
+        let piece = Piece::from_code(self.arr[pos as usize], pos);
+        if !piece.type_().is_valid() {
+            return None;
+        }
+        let king = self
+            .iter_pieces()
+            .find(|king| king.color() == piece.color() && king.type_() == PieceType::King)?;
+        self.count_pinned_pieces(king)
+            .into_iter()
+            .find(|(pinned, _)| *pinned == piece)
+            .map(|(_, pinner)| pinner)
+    }
+
+    /** The only piece between two squares, if there is exactly one */
+    #[inline]
+    fn sole_blocker(&self, from: u8, to: u8) -> Option<Piece> {
+        let mut blocker = None;
+        for pos in between(from, to) {
+            let code = self.arr[pos as usize];
+            if code != 0x00 {
+                if blocker.is_none() {
+                    blocker = Some(Piece::from_code(code, pos));
+                } else {
+                    return None;
+                }
+            }
+        }
+        blocker
+    }
+
+    /** Sliders of given color which would attack position if one piece in between was removed */
+    pub fn xray_attackers(&self, pos: u8, by: Color) -> Vec<Piece> {
+        self.iter_pieces()
+            .filter(|attacker| {
+                attacker.color() == by
+                    && attacker.slides_towards(pos)
+                    && self.sole_blocker(attacker.position, pos).is_some()
+            })
+            .collect()
+    }
+
     fn is_attacked(&self, position: u8, by_color: Color) -> bool {
         self.iter_pieces()
             .find(|piece| {
@@ -448,6 +645,392 @@ impl Board {
             .is_some()
     }
 
+    fn has_king(&self, color: Color) -> bool {
+        self.iter_pieces()
+            .any(|piece| piece.color() == color && piece.type_() == PieceType::King)
+    }
+
+    /** Both kings stand next to each other */
+    fn kings_touching(&self) -> bool {
+        let mut kings = self
+            .iter_pieces()
+            .filter(|piece| piece.type_() == PieceType::King);
+        match (kings.next(), kings.next()) {
+            (Some(first), Some(second)) => KING_MOVES
+                .iter()
+                .any(|offset| offset.wrapping_add(first.position) == second.position),
+            _ => false,
+        }
+    }
+
+    /** Check to the king of the color right after Atomic explosion, which leaves
+     * no capturing piece to give direct check. Touching kings can't be checked,
+     * as capturing one of them would explode the other too. */
+    fn atomic_check(&self, color: Color) -> CheckType {
+        let Some(king) = self
+            .iter_pieces()
+            .find(|piece| piece.color() == color && piece.type_() == PieceType::King)
+        else {
+            return CheckType::None;
+        };
+        if self.kings_touching() {
+            return CheckType::None;
+        }
+        match self
+            .who_can_attack(king)
+            .map_or(0, |attackers| attackers.len())
+        {
+            0 => CheckType::None,
+            1 => CheckType::Discovered,
+            _ => CheckType::Double,
+        }
+    }
+
+    /** Is the square attacked by any piece of the color */
+    pub fn square_attacked(&self, pos: u8, by: Color) -> bool {
+        self.is_attacked(pos, by)
+    }
+
+    /** Squares attacked by pieces of the color, indexed by `file * 8 + rank`.
+     *
+     * Pawns attack only diagonally, sliders stop at the first piece they hit. */
+    pub fn squares_attacked_by(&self, color: Color) -> [bool; 64] {
+        let mut mask = [false; 64];
+        let mut mark = |pos: u8| {
+            if is_valid_coord(pos) {
+                let (file, rank): (usize, usize) = unpack_pos(pos);
+                mask[file * 8 + rank] = true;
+            }
+        };
+        for piece in self.iter_pieces() {
+            if piece.color() != color {
+                continue;
+            }
+            let pos = piece.position;
+            match piece.type_() {
+                PieceType::Pawn => {
+                    let step: u8 = match color {
+                        Color::White => 0x10,
+                        Color::Black => 0xf0,
+                    };
+                    mark(pos.wrapping_add(step).wrapping_add(0x01));
+                    mark(pos.wrapping_add(step).wrapping_add(0xff));
+                }
+                PieceType::Knight => KNIGHT_MOVES
+                    .iter()
+                    .for_each(|offset| mark(pos.wrapping_add(*offset))),
+                PieceType::King => KING_MOVES
+                    .iter()
+                    .for_each(|offset| mark(pos.wrapping_add(*offset))),
+                sliding_piece @ (PieceType::Bishop | PieceType::Rook | PieceType::Queen) => {
+                    let directions = match sliding_piece {
+                        PieceType::Bishop => BISHOP_DIR,
+                        PieceType::Rook => ROOK_DIR,
+                        _ => QUEEN_DIR,
+                    };
+                    for dir in directions {
+                        let mut target = pos.wrapping_add(*dir);
+                        while is_valid_coord(target) {
+                            mark(target);
+                            if self.arr[target as usize] != 0x00 {
+                                break;
+                            }
+                            target = target.wrapping_add(*dir);
+                        }
+                    }
+                }
+                PieceType::Invalid | PieceType::EmptySquare => {}
+            }
+        }
+        mask
+    }
+
+    /** Manhattan distance between kings of given colors, 0 if either is missing */
+    pub fn king_tropism(&self, a: Color, b: Color) -> u8 {
+        let king = |color: Color| {
+            self.iter_pieces()
+                .find(|piece| piece.color() == color && piece.type_() == PieceType::King)
+                .map(|king| king.position)
+        };
+        match (king(a), king(b)) {
+            (Some(a), Some(b)) => distance(a, b),
+            _ => 0,
+        }
+    }
+
+    /** Counts pieces of both players in a single pass over the board */
+    pub fn scan(&self) -> BoardScan {
+        let mut scan = BoardScan::default();
+        for piece in self.iter_pieces() {
+            let piece_type = piece.type_();
+            if !piece_type.is_valid() {
+                continue;
+            }
+            match piece.color() {
+                Color::White => scan.white[piece_type as usize - 1] += 1,
+                Color::Black => scan.black[piece_type as usize - 1] += 1,
+            }
+            if piece_type == PieceType::Bishop {
+                let (file, rank): (u8, u8) = unpack_pos(piece.position);
+                scan.bishop_squares[((file + rank) % 2) as usize] = true;
+            }
+        }
+        scan
+    }
+
+    /** Pieces of both players indexed by color, Black first like `color as usize >> 7`,
+     * then by `PieceType as usize - 1` */
+    pub fn piece_counts(&self) -> [[u8; 6]; 2] {
+        let scan = self.scan();
+        [scan.black, scan.white]
+    }
+
+    /** Sum of piece values of the color, in centipawns */
+    pub fn material_count(&self, color: Color) -> i32 {
+        self.scan().material(color)
+    }
+
+    /** Game phase from 24 with all minor and major pieces on board down to 0
+     * with none of them: minor pieces weight 1, rooks 2 and queens 4. */
+    pub fn game_phase(&self) -> u8 {
+        let scan = self.scan();
+        let phase: u8 = [Color::White, Color::Black]
+            .into_iter()
+            .map(|color| {
+                scan.count(color, PieceType::Knight)
+                    + scan.count(color, PieceType::Bishop)
+                    + 2 * scan.count(color, PieceType::Rook)
+                    + 4 * scan.count(color, PieceType::Queen)
+            })
+            .sum();
+        phase.min(24)
+    }
+
+    /** Could pieces of the color checkmate by any series of moves.
+     *
+     * Lone minor piece or bishops all on squares of one color can only mate
+     * when opponent has own pieces to block their king. */
+    pub fn has_mating_material(&self, color: Color) -> bool {
+        let scan = self.scan();
+        let count = |color, piece_type| scan.count(color, piece_type);
+        if [PieceType::Pawn, PieceType::Rook, PieceType::Queen]
+            .into_iter()
+            .any(|piece_type| count(color, piece_type) > 0)
+        {
+            return true;
+        }
+        let knights = count(color, PieceType::Knight);
+        let minor_pieces = knights + count(color, PieceType::Bishop);
+        let opponent_pieces: u8 = PieceType::ALL[..5]
+            .iter()
+            .map(|&piece_type| count(color.opposite(), piece_type))
+            .sum();
+        // With bare opponent king all bishops on board are own ones
+        let can_mate_bare_king = knights > 0 || scan.bishop_squares == [true, true];
+        (minor_pieces >= 2 && can_mate_bare_king) || (minor_pieces >= 1 && opponent_pieces > 0)
+    }
+
+    /** Neither side can checkmate by any series of moves: lone kings,
+     * a single knight or only bishops all on squares of the same color. */
+    pub fn is_dead_position(&self) -> bool {
+        let scan = self.scan();
+        let total = |piece_type| {
+            scan.count(Color::White, piece_type) + scan.count(Color::Black, piece_type)
+        };
+        if [PieceType::Pawn, PieceType::Rook, PieceType::Queen]
+            .into_iter()
+            .any(|piece_type| total(piece_type) > 0)
+        {
+            return false;
+        }
+        match (total(PieceType::Knight), scan.bishop_squares) {
+            (0, [true, true]) => false,
+            (0, _) | (1, [false, false]) => true,
+            _ => false,
+        }
+    }
+
+    /** Material of White minus material of Black, in centipawns */
+    pub fn material_balance(&self) -> i32 {
+        let scan = self.scan();
+        scan.material(Color::White) - scan.material(Color::Black)
+    }
+
+    /** Same board with colors of all pieces swapped, squares are kept as is */
+    pub fn swap_colors(&self) -> Board {
+        let mut board = self.clone();
+        for &pos in ITER_INDEX.iter() {
+            if PieceType::from_byte(board.arr[pos]).is_valid() {
+                board.arr[pos] ^= 0x80;
+            }
+        }
+        board
+    }
+
+    /** King sits on its back rank boxed in by own pawns and no rook or queen guards the rank. */
+    pub fn back_rank_weakness(&self, color: Color) -> bool {
+        let (back_rank, forward) = match color {
+            Color::White => (0x00u8, 0x10u8),
+            Color::Black => (0x70u8, 0x60u8),
+        };
+        let king = self
+            .iter_pieces()
+            .find(|piece| piece.color() == color && piece.type_() == PieceType::King);
+        let Some(king) = king else {
+            return false;
+        };
+        if king.position & 0xf0 != back_rank {
+            return false;
+        }
+        let column = king.position & 0x0f;
+        let boxed_in = (column.saturating_sub(1)..=(column + 1).min(7)).all(|rank| {
+            let code = self.arr[(forward | rank) as usize];
+            PieceType::from_byte(code) == PieceType::Pawn && Color::from_byte(code) == color
+        });
+        let defended = (0..8u8).any(|rank| {
+            let code = self.arr[(back_rank | rank) as usize];
+            matches!(
+                PieceType::from_byte(code),
+                PieceType::Rook | PieceType::Queen
+            ) && Color::from_byte(code) == color
+        });
+        boxed_in && !defended
+    }
+
+    /** Number of squares piece can move to, moves into self-check are counted too. */
+    pub fn mobility_of(&self, piece: Piece) -> u8 {
+        let is_target = |pos: u8| {
+            let cell = self.arr[pos as usize];
+            cell == 0x00 || Color::from_byte(cell) != piece.color()
+        };
+        match piece.type_() {
+            PieceType::Pawn => {
+                let step: u8 = match piece.color() {
+                    Color::Black => 0xf0,
+                    Color::White => 0x10,
+                };
+                let front_pos = piece.position.wrapping_add(step);
+                if !is_valid_coord(front_pos) {
+                    return 0;
+                }
+                let captures = [0x01u8, 0xff]
+                    .iter()
+                    .map(|step| front_pos.wrapping_add(*step))
+                    .filter(|pos| {
+                        is_valid_coord(*pos)
+                            && self.arr[*pos as usize] != 0x00
+                            && Color::from_byte(self.arr[*pos as usize]) != piece.color()
+                    })
+                    .count() as u8;
+                let pushes = if self.arr[front_pos as usize] != 0x00 {
+                    0
+                } else if PieceFlag::Moved.is_set(piece.code) {
+                    1
+                } else {
+                    let pos = front_pos.wrapping_add(step);
+                    1 + (is_valid_coord(pos) && self.arr[pos as usize] == 0x00) as u8
+                };
+                captures + pushes
+            }
+            PieceType::Knight | PieceType::King => {
+                let offsets = if piece.type_() == PieceType::Knight {
+                    KNIGHT_MOVES
+                } else {
+                    KING_MOVES
+                };
+                offsets
+                    .iter()
+                    .map(|off| off.wrapping_add(piece.position))
+                    .filter(|pos| is_valid_coord(*pos) && is_target(*pos))
+                    .count() as u8
+            }
+            PieceType::Bishop | PieceType::Rook | PieceType::Queen => {
+                let possible_directions = match piece.type_() {
+                    PieceType::Bishop => BISHOP_DIR,
+                    PieceType::Rook => ROOK_DIR,
+                    _ => QUEEN_DIR,
+                };
+                let mut mobility = 0;
+                for dir in possible_directions {
+                    for pos in in_direction(piece.position, *dir) {
+                        if is_target(pos) {
+                            mobility += 1;
+                        }
+                        if self.arr[pos as usize] != 0x00 {
+                            break;
+                        }
+                    }
+                }
+                mobility
+            }
+            PieceType::Invalid | PieceType::EmptySquare => 0,
+        }
+    }
+
+    /** Atomic chess explosion of capture on the position: capturing piece and every
+     * piece but pawns on surrounding squares are removed. Returns removed pieces. */
+    pub fn explode(&mut self, pos: u8) -> Vec<Piece> {
+        let mut exploded = vec![Piece::from_code(self.arr[pos as usize], pos)];
+        for neighbour in KING_MOVES
+            .iter()
+            .map(|offset| offset.wrapping_add(pos))
+            .filter(|pos| is_valid_coord(*pos))
+        {
+            let piece = Piece::from_code(self.arr[neighbour as usize], neighbour);
+            if piece.type_().is_valid() && piece.type_() != PieceType::Pawn {
+                exploded.push(piece);
+            }
+        }
+        for piece in &exploded {
+            self.arr[piece.position()] = 0x00;
+        }
+        exploded
+    }
+
+    /** Static exchange evaluation of capture on target square.
+     *
+     * Returns material balance in centipawns for the capturing side,
+     * assuming both sides keep recapturing with least valuable piece. */
+    pub fn static_exchange(&self, capture: &Move) -> i32 {
+        // King can't be recaptured, so it's treated as very valuable
+        let value = |code: u8| match PieceType::from_byte(code) {
+            PieceType::King => 20_000,
+            _type => _type.value(),
+        };
+        let target = capture.end_position();
+        let victim = match capture.move_type() {
+            MoveType::Capture(victim)
+            | MoveType::PromotionCapture(victim, _)
+            | MoveType::EnPassantCapture(victim, _) => *victim,
+            _ => return 0,
+        };
+        let mut board = self.arr;
+        board[victim.position()] = 0x00;
+        board[capture.piece.position()] = 0x00;
+        board[target as usize] = capture.piece.code;
+        let mut gains = vec![value(victim.code)];
+        let mut on_square = value(capture.piece.code);
+        let mut side = capture.piece.color().opposite();
+        while let Some(attacker) = ITER_INDEX
+            .iter()
+            .map(|&pos| Piece::from_code(board[pos], pos as u8))
+            .filter(|piece| {
+                piece.type_().is_valid() && piece.color() == side && piece.can_attack(target, board)
+            })
+            .min_by_key(|piece| value(piece.code))
+        {
+            gains.push(on_square - gains.last().unwrap());
+            on_square = value(attacker.code);
+            board[attacker.position()] = 0x00;
+            board[target as usize] = attacker.code;
+            side = side.opposite();
+        }
+        for idx in (1..gains.len()).rev() {
+            gains[idx - 1] = -(-gains[idx - 1]).max(gains[idx]);
+        }
+        gains[0]
+    }
+
     pub fn obstruct_board(&self, player: Color) -> Vec<Vec<bool>> {
         let mut mask = Vec::with_capacity(8);
         for _ in 0..8u8 {
@@ -540,7 +1123,11 @@ impl Board {
         mask
     }
 
-    pub fn hide(mut self, point_of_view: Color) -> Self {
+    /** Board with cells the player can't see flagged as unknown.
+     *
+     * With position of the pawn which can be captured en passant, it's shown
+     * to the player's pawns next to it along with the capture square. */
+    pub fn hide(mut self, point_of_view: Color, enpassant: Option<u8>) -> Self {
         ITER_INDEX.iter().for_each(|pos| {
             self.arr[*pos] = PieceFlag::UnknownCellFlag.set(self.arr[*pos]);
         });
@@ -572,6 +1159,17 @@ impl Board {
                         self.arr[pos as usize] =
                             PieceFlag::UnknownCellFlag.unset(self.arr[pos as usize]);
                     }
+                    if let Some(pawn_pos) = enpassant {
+                        if pawn_pos & 0xf0 == piece.position & 0xf0
+                            && pawn_pos.abs_diff(piece.position) == 1
+                        {
+                            let target = pawn_pos.wrapping_add(step);
+                            self.arr[pawn_pos as usize] =
+                                PieceFlag::UnknownCellFlag.unset(self.arr[pawn_pos as usize]);
+                            self.arr[target as usize] =
+                                PieceFlag::UnknownCellFlag.unset(self.arr[target as usize]);
+                        }
+                    }
                     let cell = PieceFlag::UnknownCellFlag.unset(self.arr[front_pos as usize]);
                     self.arr[front_pos as usize] = cell;
                     if cell == 0x00 {
@@ -623,8 +1221,8 @@ impl Board {
         self
     }
 
-    pub fn hide_and_obstruct(self, point_of_view: Color) -> Self {
-        let mut board = self.hide(point_of_view);
+    pub fn hide_and_obstruct(self, point_of_view: Color, enpassant: Option<u8>) -> Self {
+        let mut board = self.hide(point_of_view, enpassant);
         ITER_INDEX.iter().for_each(|pos| {
             let code = board.arr[*pos];
             board.arr[*pos] = if PieceFlag::UnknownCellFlag.is_set(code) {
@@ -675,40 +1273,257 @@ impl Board {
         }
         compressed_board
     }
-}
 
-impl Default for Board {
-    #[rustfmt::skip]
-    fn default() -> Self {
-        Board {
-            arr: [
-                0x84, 0x82, 0x83, 0x85, 0x86, 0x83, 0x82, 0x84, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
-                0x81, 0x81, 0x81, 0x81, 0x81, 0x81, 0x81, 0x81, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
-                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
-                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
-                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
-                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
-                0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
-                0x04, 0x02, 0x03, 0x05, 0x06, 0x03, 0x02, 0x04, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
-            ]
+    /** Exact key of the board: occupancy bitmap followed by 4-bit codes of up to 32 pieces.
+     *
+     * Piece code is color bit and type, spare `Invalid` type marks a rook its king can
+     * still castle with. Moved flags aren't stored and are re-derived from squares by
+     * `unpack`, so the key holds placement and castling rights only.
+     * There's no key for boards with more pieces. */
+    pub fn pack(&self) -> Option<[u8; 24]> {
+        let mut packed = [0u8; 24];
+        let mut occupancy = 0u64;
+        let mut count = 0;
+        for (index, &pos) in ITER_INDEX.iter().enumerate() {
+            let code = self.arr[pos];
+            let piece_type = PieceType::from_byte(code);
+            if !piece_type.is_valid() {
+                continue;
+            }
+            if count == 32 {
+                return None;
+            }
+            let color = Color::from_byte(code);
+            let castling_rook = piece_type == PieceType::Rook
+                && [CastlingSide::KingSide, CastlingSide::QueenSide]
+                    .into_iter()
+                    .any(|side| {
+                        self.castling_rook(color, side)
+                            .is_some_and(|rook| rook.position == pos as u8)
+                    });
+            let nibble = (code & 0x80) >> 4
+                | if castling_rook {
+                    PieceType::Invalid as u8
+                } else {
+                    piece_type as u8
+                };
+            occupancy |= 1 << index;
+            packed[8 + count / 2] |= nibble << (count % 2 * 4);
+            count += 1;
         }
+        packed[..8].copy_from_slice(&occupancy.to_le_bytes());
+        Some(packed)
     }
-}
 
-const ITER_INDEX: [usize; 64] = {
-    let mut arr = [0; 64];
-    let mut rank = 0;
-    let mut file = 0;
-    while rank < 8 {
-        arr[file * 8 + rank] = file << 4 | rank;
-        if file < 7 {
-            file += 1;
-        } else {
-            file = 0;
-            rank += 1;
-        }
-    }
-    arr
+    /** Board from `pack` key, see it for what is preserved */
+    pub fn unpack(packed: &[u8; 24]) -> Board {
+        let mut board = Board::new();
+        let occupancy = u64::from_le_bytes(packed[..8].try_into().unwrap());
+        let mut castling_rooks = Vec::with_capacity(4);
+        let mut count = 0;
+        for (index, &pos) in ITER_INDEX.iter().enumerate() {
+            if occupancy & 1 << index == 0 {
+                continue;
+            }
+            let nibble = packed[8 + count / 2] >> (count % 2 * 4) & 0x0f;
+            count += 1;
+            let color = Color::from_byte(nibble << 4);
+            let piece_type = match PieceType::from_byte(nibble) {
+                PieceType::Invalid => PieceType::Rook,
+                piece_type => piece_type,
+            };
+            board.arr[pos] = color as u8 | piece_type as u8;
+            if PieceType::from_byte(nibble) == PieceType::Invalid {
+                castling_rooks.push((color, pos as u8));
+            } else {
+                board.arr[pos] |= flag_piece_moved(piece_type, color, pos as u8);
+            }
+        }
+        // Rooks may come before their king, so rights are given afterwards
+        for (color, pos) in castling_rooks {
+            let row = castling_row(color);
+            let Some(king_pos) = (row..row + 8).find(|&king_pos| {
+                let code = board.arr[king_pos as usize];
+                PieceType::from_byte(code) == PieceType::King && Color::from_byte(code) == color
+            }) else {
+                continue;
+            };
+            let (rights, outer_files) = if pos > king_pos {
+                (PieceFlag::CanCastleKingSide, pos + 1..row + 8)
+            } else {
+                (PieceFlag::CanCastleQueenSide, row..pos)
+            };
+            let king = &mut board.arr[king_pos as usize];
+            *king = rights.set(PieceFlag::Moved.unset(*king));
+            // Only the outermost unmoved rook is castled with
+            for outer in outer_files {
+                if PieceType::from_byte(board.arr[outer as usize]) == PieceType::Rook {
+                    board.arr[outer as usize] = PieceFlag::Moved.set(board.arr[outer as usize]);
+                }
+            }
+        }
+        board
+    }
+
+    /** Rook the king of this color still castles with to given side, judging by kings and
+     * rooks flags. It's the outermost unmoved rook on that side of the king, which stands
+     * on any file of its first rank to support Chess960. */
+    pub fn castling_rook(&self, color: Color, side: CastlingSide) -> Option<Piece> {
+        let piece_flag = match side {
+            CastlingSide::KingSide => PieceFlag::CanCastleKingSide,
+            CastlingSide::QueenSide => PieceFlag::CanCastleQueenSide,
+        };
+        let row = castling_row(color);
+        let king_pos = (row..row + 8).find(|&pos| {
+            let code = self.arr[pos as usize];
+            PieceType::from_byte(code) == PieceType::King && Color::from_byte(code) == color
+        })?;
+        let king = self.arr[king_pos as usize];
+        if PieceFlag::Moved.is_set(king) || !piece_flag.is_set(king) {
+            return None;
+        }
+        let is_castling_rook = |pos: &u8| {
+            let code = self.arr[*pos as usize];
+            PieceType::from_byte(code) == PieceType::Rook
+                && Color::from_byte(code) == color
+                && !PieceFlag::Moved.is_set(code)
+        };
+        let rook_pos = match side {
+            CastlingSide::KingSide => (king_pos + 1..row + 8).rev().find(is_castling_rook),
+            CastlingSide::QueenSide => (row..king_pos).find(is_castling_rook),
+        }?;
+        Some(Piece::from_code(self.arr[rook_pos as usize], rook_pos))
+    }
+
+    /** Can king of this color still castle to given side, judging by kings and rooks flags */
+    fn can_castle(&self, color: Color, side: CastlingSide) -> bool {
+        self.castling_rook(color, side).is_some()
+    }
+
+    /** Castling rights judging by flags of kings and rooks */
+    pub fn castling_rights(&self) -> CastlingRights {
+        CastlingRights {
+            white_king_side: self.can_castle(Color::White, CastlingSide::KingSide),
+            white_queen_side: self.can_castle(Color::White, CastlingSide::QueenSide),
+            black_king_side: self.can_castle(Color::Black, CastlingSide::KingSide),
+            black_queen_side: self.can_castle(Color::Black, CastlingSide::QueenSide),
+        }
+    }
+
+    /** Zobrist key of the position, computed from scratch.
+     *
+     * Besides pieces placement it takes player to move, castling rights and
+     * en passant target square, so boards with same placement may differ. */
+    pub fn zobrist(&self, player: Color, rights: CastlingRights, en_passant: Option<u8>) -> u64 {
+        let mut key = match player {
+            Color::White => 0,
+            Color::Black => zobrist::BLACK_TO_MOVE,
+        };
+        for (square, &pos) in ITER_INDEX.iter().enumerate() {
+            let code = self.arr[pos];
+            if PieceType::from_byte(code).is_valid() {
+                key ^= zobrist::PIECE_KEYS[square][zobrist::piece_index(code)];
+            }
+        }
+        for (i, right) in rights.as_array().into_iter().enumerate() {
+            if right {
+                key ^= zobrist::CASTLING_KEYS[i];
+            }
+        }
+        if let Some(pos) = en_passant {
+            key ^= zobrist::EN_PASSANT_KEYS[(pos & 0x0f) as usize];
+        }
+        key
+    }
+}
+
+/** Which castlings are still available for both players */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CastlingRights {
+    pub white_king_side: bool,
+    pub white_queen_side: bool,
+    pub black_king_side: bool,
+    pub black_queen_side: bool,
+}
+
+impl CastlingRights {
+    /** Rights in FEN order: `K`, `Q`, `k`, `q` */
+    fn as_array(&self) -> [bool; 4] {
+        [
+            self.white_king_side,
+            self.white_queen_side,
+            self.black_king_side,
+            self.black_queen_side,
+        ]
+    }
+
+    /** King side and queen side rights of one player */
+    pub fn of(&self, color: Color) -> (bool, bool) {
+        match color {
+            Color::White => (self.white_king_side, self.white_queen_side),
+            Color::Black => (self.black_king_side, self.black_queen_side),
+        }
+    }
+}
+
+/** Piece counts of both players, indexed by `PieceType as usize - 1` */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BoardScan {
+    pub white: [u8; 6],
+    pub black: [u8; 6],
+    /** Are there bishops on dark and light squares */
+    pub bishop_squares: [bool; 2],
+}
+
+impl BoardScan {
+    pub fn count(&self, color: Color, piece_type: PieceType) -> u8 {
+        match color {
+            Color::White => self.white[piece_type as usize - 1],
+            Color::Black => self.black[piece_type as usize - 1],
+        }
+    }
+
+    /** Sum of piece values of the color, in centipawns */
+    pub fn material(&self, color: Color) -> i32 {
+        PieceType::ALL
+            .iter()
+            .map(|&piece_type| self.count(color, piece_type) as i32 * piece_type.value())
+            .sum()
+    }
+}
+
+impl Default for Board {
+    #[rustfmt::skip]
+    fn default() -> Self {
+        Board {
+            arr: [
+                0x84, 0x82, 0x83, 0x85, 0xB6, 0x83, 0x82, 0x84, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+                0x81, 0x81, 0x81, 0x81, 0x81, 0x81, 0x81, 0x81, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+                0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+                0x04, 0x02, 0x03, 0x05, 0x36, 0x03, 0x02, 0x04, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+            ]
+        }
+    }
+}
+
+const ITER_INDEX: [usize; 64] = {
+    let mut arr = [0; 64];
+    let mut rank = 0;
+    let mut file = 0;
+    while rank < 8 {
+        arr[file * 8 + rank] = file << 4 | rank;
+        if file < 7 {
+            file += 1;
+        } else {
+            file = 0;
+            rank += 1;
+        }
+    }
+    arr
 };
 
 /** Tables directions for pieces */
@@ -720,7 +1535,10 @@ const QUEEN_DIR: &[u8] = &[0x11, 0x0f, 0xef, 0xf1, 0x10, 0xff, 0xf0, 0x01];
 const KING_MOVES: &[u8] = QUEEN_DIR;
 const KNIGHT_MOVES: &[u8] = &[0x12, 0x21, 0x1f, 0x0e, 0xee, 0xdf, 0xe1, 0xf2];
 
-#[derive(Clone, Debug)]
+/** Standard initial position, with castling rights */
+const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum GameHistory {
     LastMove(Option<Move>),
     FullHistory(Vec<Move>),
@@ -762,15 +1580,20 @@ impl GameHistory {
         }
     }
 
+    /** Last move only, moves recorded after it are kept to be undone one by one */
     fn light_clone(&self) -> Self {
-        GameHistory::LastMove(self.last_move())
+        GameHistory::FullHistory(self.last_move().into_iter().collect())
     }
 }
 
-#[derive(Clone, Default, Debug)]
+/** Keys of positions after every executed move of the game. Key covers
+ * side to move, castling rights and en passant square, so equal keys
+ * are the same position by repetition rules. */
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
 struct ExistedPositions {
-    boards: Vec<CompressedBoard>,
-    offsets: Vec<usize>,
+    keys: Vec<u64>,
+    /** How many times each key occurs in `keys` */
+    counts: HashMap<u64, u8>,
 }
 
 impl ExistedPositions {
@@ -778,46 +1601,143 @@ impl ExistedPositions {
         Default::default()
     }
 
-    fn push(&mut self, board: CompressedBoard) {
-        self.boards.push(board);
+    fn push(&mut self, key: u64) {
+        self.keys.push(key);
+        let count = self.counts.entry(key).or_default();
+        *count = count.saturating_add(1);
     }
 
-    fn count(&mut self, board: &CompressedBoard) -> usize {
-        self.boards
-            .iter()
-            .skip(*self.offsets.last().unwrap_or(&0))
-            .filter(|exboard| *exboard == board)
-            .count()
+    fn count(&self, key: u64) -> usize {
+        self.counts.get(&key).copied().unwrap_or_default() as usize
     }
 
-    fn clear(&mut self) {
-        self.offsets.push(self.boards.len());
+    fn occurrences(&self) -> Vec<(u64, usize)> {
+        let mut occurrences: Vec<(u64, usize)> = Vec::with_capacity(self.counts.len());
+        for key in &self.keys {
+            if !occurrences.iter().any(|(exkey, _)| exkey == key) {
+                occurrences.push((*key, self.count(*key)));
+            }
+        }
+        occurrences
     }
 
-    fn undo_move(&mut self, move_type: MoveType) {
-        self.boards.pop();
-        if !matches!(move_type, MoveType::QuietMove(_)) {
-            self.offsets.pop();
+    fn undo_move(&mut self) {
+        let Some(key) = self.keys.pop() else {
+            return;
+        };
+        if let Some(count) = self.counts.get_mut(&key) {
+            *count -= 1;
+            if *count == 0 {
+                self.counts.remove(&key);
+            }
         }
     }
 }
 
-#[derive(Clone, Default, Debug)]
+/** Whole local game, serializable to be saved and resumed later */
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Game {
     board: Board,
     current_player: Color,
     existed_positions: ExistedPositions,
     history: GameHistory,
+    /** Plies since last capture or pawn move */
+    halfmove_clock: u16,
+    /** Starts at 1 and incremented after Black's move */
+    fullmove_number: u16,
+    /** Halfmove clock values before each executed move, to restore them on undo */
+    clock_history: Vec<u16>,
+    mode: GameMode,
+    /** Checks delivered by Black and White */
+    check_counts: [u8; 2],
+    clock: Option<Clock>,
+    /** Clock before each executed move, to restore it on undo */
+    clock_states: Vec<Clock>,
+    /** Pieces removed by explosions of Atomic captures, to restore them on undo */
+    explosions: Vec<Vec<Piece>>,
+    /** What the last executed move ended the game with */
+    end_state: Option<GameEndState>,
+    /** Possible moves of the current position, filled on demand */
+    #[serde(skip)]
+    moves_cache: OnceLock<Vec<Move>>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self {
+            board: Default::default(),
+            current_player: Default::default(),
+            existed_positions: Default::default(),
+            history: GameHistory::FullHistory(Vec::new()),
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            clock_history: Vec::new(),
+            mode: GameMode::Standard,
+            check_counts: [0; 2],
+            clock: None,
+            clock_states: Vec::new(),
+            explosions: Vec::new(),
+            end_state: None,
+            moves_cache: OnceLock::new(),
+        }
+    }
+}
+
+/** Why move from a line can't be played, with index of the move */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MoveError {
+    Illegal(usize),
+    GameEnded(usize),
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+/** Why FEN string can't be read */
+#[derive(Clone, Debug, PartialEq)]
+pub enum FenError {
+    /** Required field or its part is missing, with name of the field */
+    Truncated(&'static str),
+    /** Field has unexpected content, with description of the problem */
+    Invalid(String),
+}
+
+impl Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FenError::Truncated(field) => write!(f, "FEN string is truncated in {field}"),
+            FenError::Invalid(reason) => f.write_str(reason),
+        }
+    }
+}
+
+/** Rules game is played by */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum GameMode {
+    #[default]
+    Standard,
+    /** King reaching one of four central squares wins */
+    KingOfTheHill,
+    /** Giving the third check wins */
+    ThreeCheck,
+    /** Captures explode surrounding pieces, exploding enemy king wins */
+    Atomic,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum GameEndState {
     CheckMate(Color),
+    /** Winner by rules of non-standard game mode */
+    VariantWin(Color),
+    /** Winner, who gave the third check in Three-check mode */
+    ThreeCheckWin(Color),
+    /** Winner, whose opponent ran out of time */
+    TimeForfeit(Color),
+    /** Winner, whose opponent resigned */
     Resignation(Color),
     DrawStalemate,
     DrawThreefoldRepetition,
     DrawFiftyMoveRule,
     DrawInsufficientMaterial,
+    /** Both players agreed to a draw */
+    DrawAgreement,
 }
 
 fn flag_piece_moved(piece: PieceType, color: Color, pos: u8) -> u8 {
@@ -844,6 +1764,63 @@ fn flag_piece_moved(piece: PieceType, color: Color, pos: u8) -> u8 {
     }
 }
 
+/** Puts check state of player to move into the initial last move, inventing one if needed */
+fn annotate_initial_check(
+    board: &Board,
+    current_player: Color,
+    mut last_move: Option<Move>,
+) -> Option<Move> {
+    let king = board
+        .iter_pieces()
+        .find(|piece| piece.color() == current_player && piece.type_() == PieceType::King)
+        .expect("The player should have a king to move.");
+    let attackers: Vec<_> = board
+        .iter_pieces()
+        .filter(|piece| {
+            piece.color() != current_player
+                && piece.type_() != PieceType::EmptySquare
+                && piece.can_attack(king.position, board.arr)
+        })
+        .collect();
+    if let Some(check) = match attackers.len() {
+        0 => None,
+        1 if last_move
+            .as_ref()
+            .map_or(false, |_move| _move.end_position() == attackers[0].position) =>
+        {
+            Some(CheckType::Direct)
+        }
+        1 => Some(CheckType::Discovered),
+        _ => Some(CheckType::Double),
+    } {
+        last_move = last_move.map_or(
+            Some(Move {
+                piece: Piece::from_code(0xff, 0xff),
+                move_type: MoveType::QuietMove(0xff),
+                check,
+            }),
+            |last_move| Some(Move { check, ..last_move }),
+        )
+    }
+    last_move
+}
+
+/** Row where pieces of given color start and castle */
+fn castling_row(color: Color) -> u8 {
+    match color {
+        Color::White => 0x00,
+        Color::Black => 0x70,
+    }
+}
+
+pub(crate) fn fen_letter(piece: Piece) -> char {
+    let letter = piece.type_().letter();
+    match piece.color() {
+        Color::White => letter.to_ascii_uppercase(),
+        Color::Black => letter,
+    }
+}
+
 impl Game {
     pub fn new(board: Board, current_player: Color, last_move: Option<Move>) -> Game {
         Game {
@@ -858,8 +1835,60 @@ impl Game {
         }
     }
 
-    pub fn from_fen(fen: &str) -> Result<Game, String> {
-        let mut chars = fen.chars();
+    /** Fischer Random starting position by its number in standard 0..960 numbering,
+     * 518 is the classical start. Back rank is mirrored for Black and kings get both
+     * castling rights.
+     *
+     * Panics if `position_id` isn't below 960. */
+    pub fn chess960(position_id: u16) -> Game {
+        assert!(position_id < 960, "Chess960 position id must be below 960");
+        const KNIGHTS: [(usize, usize); 10] = [
+            (0, 1),
+            (0, 2),
+            (0, 3),
+            (0, 4),
+            (1, 2),
+            (1, 3),
+            (1, 4),
+            (2, 3),
+            (2, 4),
+            (3, 4),
+        ];
+        let mut back_rank = [' '; 8];
+        let mut id = position_id as usize;
+        // Bishops go on light and dark squares first
+        back_rank[id % 4 * 2 + 1] = 'b';
+        id /= 4;
+        back_rank[id % 4 * 2] = 'b';
+        id /= 4;
+        let empty = |back_rank: &[char; 8]| -> Vec<usize> {
+            (0..8).filter(|&rank| back_rank[rank] == ' ').collect()
+        };
+        back_rank[empty(&back_rank)[id % 6]] = 'q';
+        id /= 6;
+        let (first, second) = KNIGHTS[id];
+        let free = empty(&back_rank);
+        back_rank[free[first]] = 'n';
+        back_rank[free[second]] = 'n';
+        // King always stands between rooks
+        for (rank, letter) in empty(&back_rank).into_iter().zip(['r', 'k', 'r']) {
+            back_rank[rank] = letter;
+        }
+        let black: String = back_rank.iter().collect();
+        let fen = format!(
+            "{black}/pppppppp/8/8/8/8/PPPPPPPP/{} w KQkq - 0 1",
+            black.to_ascii_uppercase()
+        );
+        Game::from_fen(&fen).expect("Chess960 position is valid")
+    }
+
+    pub fn from_fen(fen: &str) -> Result<Game, FenError> {
+        // Fields may be separated by any whitespace, trailing ones are optional
+        let mut fields = fen.split_whitespace();
+        let mut chars = fields
+            .next()
+            .ok_or(FenError::Truncated("piece placement"))?
+            .chars();
         let mut board = Board::new();
         // Board portion
         for file in (0..8).rev() {
@@ -869,49 +1898,47 @@ impl Game {
                     rank += num;
                     continue;
                 }
-                if rank == 8 {
+                if rank == 8 && letter == '/' && file != 0 {
                     break;
                 }
-                let color: Color = if letter.is_uppercase() {
-                    Color::White
-                } else {
-                    Color::Black
-                };
-                let piece: PieceType = match letter {
-                    'p' | 'P' => PieceType::Pawn,
-                    'r' | 'R' => PieceType::Rook,
-                    'n' | 'N' => PieceType::Knight,
-                    'b' | 'B' => PieceType::Bishop,
-                    'q' | 'Q' => PieceType::Queen,
-                    'k' | 'K' => PieceType::King,
-                    // This point should not be reached,
-                    //  because final iteration of loop will consume '/' or whitespace
-                    // '/' => break,
-                    _ => {
-                        return Err(format!(
-                            "Unexpected symbol '{letter}' during parsing board layout"
-                        ))
-                    }
-                };
+                if rank >= 8 {
+                    return Err(FenError::Invalid(format!(
+                        "Too many squares in rank {}",
+                        file + 1
+                    )));
+                }
+                let color = Color::from_fen_char(letter);
+                // Separators are consumed by the final iteration of loop,
+                //  so anything else here is an error
+                let piece = PieceType::try_from(letter).map_err(|letter| {
+                    FenError::Invalid(format!(
+                        "Unexpected symbol '{letter}' during parsing board layout"
+                    ))
+                })?;
                 let pos = compact_pos(file as u8, rank as u8);
                 board.arr[pos as usize] =
                     piece as u8 | color as u8 | flag_piece_moved(piece, color, pos);
                 rank += 1;
             }
+            if rank != 8 {
+                return Err(FenError::Invalid(format!(
+                    "Rank {} has {rank} squares instead of 8",
+                    file + 1
+                )));
+            }
         }
         // Active player
-        let current_player = match chars.next() {
-            Some('w') => Color::White,
-            Some('b') => Color::Black,
-            Some(letter) => {
-                return Err(format!(
-                    "Unexpected symbol '{letter}' during parsing active player"
-                ))
+        let current_player = match fields.next() {
+            Some("w") => Color::White,
+            Some("b") => Color::Black,
+            Some(field) => {
+                return Err(FenError::Invalid(format!(
+                    "Unexpected symbols '{field}' during parsing active player"
+                )))
             }
-            None => return Err("String exhausted too early".to_string()),
+            None => return Err(FenError::Truncated("active player")),
         };
-        chars.next();
-        // Castling availability
+        // Castling availability, none if missing
         let mut rights = 0u8;
         let mut rights_color = Color::White;
         let update_king = |board: &mut Board, color: Color, rights: u8| {
@@ -921,12 +1948,33 @@ impl Game {
             {
                 king
             } else {
-                return Err(format!("Can't find {color} king"));
+                return Err(FenError::Invalid(format!("Can't find {color} king")));
             };
             board.arr[king.position()] = king.code | rights;
+            // Chess960 king and rooks may start off classical squares, rights name the
+            // outermost rooks on both sides of the king, like in X-FEN
+            let row = castling_row(color);
+            if rights == 0 || king.position & 0xf0 != row {
+                return Ok(());
+            }
+            board.arr[king.position()] = PieceFlag::Moved.unset(king.code | rights);
+            let is_rook = |pos: &u8| {
+                let code = board.arr[*pos as usize];
+                PieceType::from_byte(code) == PieceType::Rook && Color::from_byte(code) == color
+            };
+            let king_side = (king.position + 1..row + 8).rev().find(is_rook);
+            let queen_side = (row..king.position).find(is_rook);
+            for (piece_flag, rook_pos) in [
+                (PieceFlag::CanCastleKingSide, king_side),
+                (PieceFlag::CanCastleQueenSide, queen_side),
+            ] {
+                if let Some(pos) = rook_pos.filter(|_| piece_flag.is_set(rights)) {
+                    board.arr[pos as usize] = PieceFlag::Moved.unset(board.arr[pos as usize]);
+                }
+            }
             Ok(())
         };
-        while let Some(letter) = chars.next() {
+        for letter in fields.next().unwrap_or("-").chars() {
             if letter.is_lowercase() && rights_color == Color::White {
                 update_king(&mut board, rights_color, rights)?;
                 rights = 0;
@@ -936,28 +1984,33 @@ impl Game {
                 'Q' | 'q' => rights |= PieceFlag::CanCastleQueenSide as u8,
                 'K' | 'k' => rights |= PieceFlag::CanCastleKingSide as u8,
                 '-' => continue,
-                ' ' => break,
                 _ => {
-                    return Err(format!(
+                    return Err(FenError::Invalid(format!(
                         "Unexpected symbol '{letter}' during parsing castling rights"
-                    ))
+                    )))
                 }
             }
         }
         update_king(&mut board, rights_color, rights)?;
-        // En Passant target square
-        let mut last_move = match chars.next() {
-            Some('-') => None,
-            Some(letter) => {
-                let rank = match letter.to_ascii_lowercase() {
-                    'a' | 'b' | 'c' | 'd' | 'e' | 'f' | 'g' | 'h' => letter as u8 - 'a' as u8,
-                    _ => return Err("Error in move rank".to_string()),
+        // En Passant target square, none if missing
+        let last_move = match fields.next().unwrap_or("-") {
+            "-" => None,
+            square if square.len() < 2 => return Err(FenError::Truncated("en passant square")),
+            square => {
+                let target = str_to_pos(square).ok_or_else(|| {
+                    FenError::Invalid(format!("Malformed en passant square '{square}'"))
+                })?;
+                // Pawn stands in front of the target square
+                let pos = match target & 0xf0 {
+                    0x20 => target + 0x10,
+                    0x50 => target - 0x10,
+                    _ => {
+                        return Err(FenError::Invalid(format!(
+                            "En passant can't be on rank {}",
+                            (target >> 4) + 1
+                        )))
+                    }
                 };
-                let mut file = chars.next().unwrap() as u8 - '0' as u8;
-                if current_player == Color::White {
-                    file -= 2;
-                }
-                let pos = compact_pos(file, rank);
                 let piece = Piece::from_code(board.arr[pos as usize], pos);
                 if piece.type_() == PieceType::Pawn {
                     Some(Move {
@@ -969,41 +2022,21 @@ impl Game {
                     None
                 }
             }
-            None => return Err("FEN string ended too early".to_string()),
         };
-        let king = board
-            .iter_pieces()
-            .find(|piece| piece.color() == current_player && piece.type_() == PieceType::King)
-            .expect("The player should have a king to move.");
-        let attackers: Vec<_> = board
-            .iter_pieces()
-            .filter(|piece| {
-                piece.color() != current_player
-                    && piece.type_() != PieceType::EmptySquare
-                    && piece.can_attack(king.position, board.arr)
-            })
-            .collect();
-        if let Some(check) = match attackers.len() {
-            0 => None,
-            1 if last_move
-                .as_ref()
-                .map_or(false, |_move| _move.end_position() == attackers[0].position) =>
-            {
-                Some(CheckType::Direct)
-            }
-            1 => Some(CheckType::Discovered),
-            _ => Some(CheckType::Double),
-        } {
-            last_move = last_move.map_or(
-                Some(Move {
-                    piece: Piece::from_code(0xff, 0xff),
-                    move_type: MoveType::QuietMove(0xff),
-                    check,
-                }),
-                |last_move| Some(Move { check, ..last_move }),
-            )
-        }
-        // The rest is currently ignored
+        // Clocks are optional
+        let halfmove_clock = match fields.next() {
+            Some(value) => value
+                .parse()
+                .map_err(|_| FenError::Invalid(format!("Incorrect halfmove clock '{value}'")))?,
+            None => 0,
+        };
+        let fullmove_number = match fields.next() {
+            Some(value) => value
+                .parse()
+                .map_err(|_| FenError::Invalid(format!("Incorrect fullmove number '{value}'")))?,
+            None => 1,
+        };
+        let last_move = annotate_initial_check(&board, current_player, last_move);
         Ok(Self {
             board,
             current_player,
@@ -1013,16 +2046,228 @@ impl Game {
             } else {
                 Vec::new()
             }),
+            halfmove_clock,
+            fullmove_number,
+            clock_history: Vec::new(),
+            mode: GameMode::Standard,
+            check_counts: [0; 2],
+            clock: None,
+            clock_states: Vec::new(),
+            explosions: Vec::new(),
+            end_state: None,
+            moves_cache: OnceLock::new(),
         })
     }
 
+    /** Random position with both kings and up to `max_pieces` pieces in total,
+     * which passes `validate`. No castling rights or en passant are given. */
+    pub fn random_legal_position<R: Rng>(rng: &mut R, max_pieces: usize) -> Game {
+        const PIECES: [PieceType; 5] = [
+            PieceType::Pawn,
+            PieceType::Knight,
+            PieceType::Bishop,
+            PieceType::Rook,
+            PieceType::Queen,
+        ];
+        loop {
+            let mut board = Board::new();
+            let mut squares: Vec<_> = ITER_INDEX.to_vec();
+            squares.shuffle(rng);
+            let extra = rng.gen_range(0..=max_pieces.clamp(2, 32) - 2);
+            for (i, pos) in squares.into_iter().take(extra + 2).enumerate() {
+                let (piece_type, color) = match i {
+                    0 => (PieceType::King, Color::White),
+                    1 => (PieceType::King, Color::Black),
+                    _ => {
+                        let color = if rng.gen() {
+                            Color::White
+                        } else {
+                            Color::Black
+                        };
+                        // No pawns on first and last ranks
+                        let on_back_rank = matches!(pos & 0xf0, 0x00 | 0x70);
+                        let piece_type = *PIECES[on_back_rank as usize..].choose(rng).unwrap();
+                        (piece_type, color)
+                    }
+                };
+                board.arr[pos] =
+                    color as u8 | piece_type as u8 | flag_piece_moved(piece_type, color, pos as u8);
+            }
+            let current_player = if rng.gen() {
+                Color::White
+            } else {
+                Color::Black
+            };
+            let last_move = annotate_initial_check(&board, current_player, None);
+            let game = Game {
+                board,
+                current_player,
+                history: GameHistory::FullHistory(last_move.into_iter().collect()),
+                ..Default::default()
+            };
+            if game.validate().is_ok() {
+                return game;
+            }
+        }
+    }
+
+    /** Checks that position is reachable in principle: one king for each side,
+     * no pawns on first or last ranks, player who just moved isn't in check
+     * and player to move isn't checked by more than two pieces. */
+    pub fn validate(&self) -> Result<(), String> {
+        let mut kings = [None, None];
+        for piece in self.board.iter_pieces() {
+            if piece.type_() == PieceType::Pawn && matches!(piece.position & 0xf0, 0x00 | 0x70) {
+                return Err(format!(
+                    "Pawn can't stand on {}",
+                    pos_to_str(piece.position)
+                ));
+            }
+            if piece.type_() == PieceType::King {
+                let king = &mut kings[(piece.color() == Color::White) as usize];
+                if king.is_some() {
+                    return Err(format!("{} has more than one king", piece.color()));
+                }
+                *king = Some(piece);
+            }
+        }
+        let checkers = |color: Color| match kings[(color == Color::White) as usize] {
+            Some(king) => Ok(self
+                .board
+                .who_can_attack(king)
+                .map_or(0, |attackers| attackers.len())),
+            None => Err(format!("{color} has no king")),
+        };
+        if checkers(self.current_player.opposite())? != 0 {
+            return Err(format!(
+                "{} is in check on opponent's turn",
+                self.current_player.opposite()
+            ));
+        }
+        if checkers(self.current_player)? > 2 {
+            return Err(format!(
+                "{} is checked by too many pieces",
+                self.current_player
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::with_capacity(90);
+        // Board portion
+        for file in (0..8u8).rev() {
+            let mut empty = 0;
+            for rank in 0..8u8 {
+                let piece = self.board.get(file, rank);
+                if !piece.type_().is_valid() {
+                    empty += 1;
+                    continue;
+                }
+                if empty != 0 {
+                    fen.push(char::from_digit(empty, 10).unwrap());
+                    empty = 0;
+                }
+                fen.push(fen_letter(piece));
+            }
+            if empty != 0 {
+                fen.push(char::from_digit(empty, 10).unwrap());
+            }
+            if file != 0 {
+                fen.push('/');
+            }
+        }
+        // Active player
+        fen.push_str(match self.current_player {
+            Color::White => " w ",
+            Color::Black => " b ",
+        });
+        // Castling availability
+        let mut any_rights = false;
+        let rights = self.board.castling_rights().as_array();
+        for (right, letter) in zip(rights, ['K', 'Q', 'k', 'q']) {
+            if right {
+                fen.push(letter);
+                any_rights = true;
+            }
+        }
+        if !any_rights {
+            fen.push('-');
+        }
+        // En Passant target square
+        fen.push(' ');
+        match self.en_passant_target() {
+            Some(pos) => fen.push_str(pos_to_str(pos)),
+            None => fen.push('-'),
+        }
+        fen.push_str(&format!(
+            " {} {}",
+            self.halfmove_clock, self.fullmove_number
+        ));
+        fen
+    }
+
+    /** Zobrist key of the current position. En passant target is part of it only
+     * when the capture there is legal, otherwise position is the same as without it. */
+    fn position_key(&self) -> u64 {
+        let en_passant = self.en_passant_target().filter(|_| {
+            self.legal_moves_cached()
+                .iter()
+                .any(|_move| matches!(_move.move_type(), MoveType::EnPassantCapture(..)))
+        });
+        self.board.zobrist(
+            self.current_player,
+            self.board.castling_rights(),
+            en_passant,
+        )
+    }
+
+    /** Square behind the pawn that just made double push, where it can be captured
+     * en passant. Like in FEN, it's given even if no pawn can capture there. */
+    pub fn en_passant_target(&self) -> Option<u8> {
+        let last_move = self.history.last_move()?;
+        match last_move.move_type() {
+            MoveType::PawnDoublePush(pos) => Some(match last_move.piece().color() {
+                Color::White => pos.wrapping_sub(0x10),
+                Color::Black => pos.wrapping_add(0x10),
+            }),
+            _ => None,
+        }
+    }
+
+    /** Can the player still castle king side and queen side, judging by flags
+     * of their king and rooks. Squares being empty or attacked isn't checked. */
+    pub fn castling_rights(&self, color: Color) -> (bool, bool) {
+        self.board.castling_rights().of(color)
+    }
+
+    /** Pawn that just made double push */
+    fn en_passant_pawn(&self) -> Option<u8> {
+        let last_move = self.history.last_move()?;
+        match last_move.move_type() {
+            MoveType::PawnDoublePush(_) => Some(last_move.end_position()),
+            _ => None,
+        }
+    }
+
+    #[deprecated(note = "use `legal_moves` or `legal_moves_expanded` instead")]
     pub fn get_possible_moves(&self, bot: bool) -> Vec<Move> {
+        self.generate_moves(bot, false, false)
+    }
+
+    /** Any legal move of the current player, generation stops as soon as one is found */
+    pub fn first_legal_move(&self) -> Option<Move> {
+        self.moves_by_rules(false, true).into_iter().next()
+    }
+
+    /** Move generator, with `first_only` it stops after the first piece
+     * which has a legal move, so the result is a short non-empty prefix.
+     *
+     * With `pseudo_legal` pins, checks and attacked squares aren't looked at,
+     * so variants with own legality rules can filter moves themselves. */
+    fn generate_moves(&self, bot: bool, first_only: bool, pseudo_legal: bool) -> Vec<Move> {
         // Check for pawn double push
-        let last_move = self.history.last_move();
-        let enpassant_pawn = match last_move.as_ref().map(|_move| _move.move_type) {
-            Some(MoveType::PawnDoublePush(_)) => Some(last_move.unwrap().end_position()),
-            _ => None,
-        };
+        let enpassant_pawn = self.en_passant_pawn();
         let mut possible_moves = Vec::with_capacity(256);
         // Count pinned pieces
         let (king, enemy_king) = {
@@ -1045,14 +2290,66 @@ impl Game {
                 enemy_king,
             )
         };
-        let king_in_check = self.history.last_move().map(|_move| _move.check);
-        let pinned_pieces = self.board.count_pinned_pieces(king);
+        let king_in_check = if pseudo_legal {
+            // Castling still can't start from check
+            Some(self.board.atomic_check(self.current_player))
+        } else {
+            self.history.last_move().map(|_move| _move.check)
+        };
+        let pinned_pieces = if pseudo_legal {
+            Vec::new()
+        } else {
+            self.board.count_pinned_pieces(king)
+        };
+        // Only moves to these positions can resolve a single check
+        let check_block = if !pseudo_legal
+            && matches!(
+                king_in_check,
+                Some(CheckType::Direct) | Some(CheckType::Discovered)
+            ) {
+            let attack_pieces = self
+                .board
+                .who_can_attack(king)
+                .expect("Incorrect check: attacker is not found");
+            assert!(
+                attack_pieces.len() == 1,
+                "There can be only one piece to attack the king"
+            );
+            let attacker = attack_pieces.into_iter().next().unwrap();
+            let possible_positions: Vec<_> = if matches!(
+                attacker.type_(),
+                PieceType::Bishop | PieceType::Queen | PieceType::Rook
+            ) {
+                between(attacker.position, king.position).collect()
+            } else {
+                Vec::new()
+            };
+            Some((attacker, possible_positions))
+        } else {
+            None
+        };
+        let resolves_check = |_move: &Move| {
+            let Some((attacker, possible_positions)) = &check_block else {
+                return true;
+            };
+            match _move.move_type() {
+                _ if _move.piece().type_() == PieceType::King => true,
+                MoveType::EnPassantCapture(pawn, _) if pawn == attacker => true,
+                _ => {
+                    _move.end_position() == attacker.position
+                        || possible_positions.contains(&_move.end_position())
+                }
+            }
+        };
 
         for piece in self
             .board
             .iter_pieces()
             .filter(|piece| piece.color() == self.current_player && piece.type_().is_valid())
         {
+            if first_only && possible_moves.iter().any(resolves_check) {
+                break;
+            }
             let (attacker, possible_positions) = if let Some((_, attacker)) = pinned_pieces
                 .iter()
                 .find(|(pinned_piece, _)| *pinned_piece == piece)
@@ -1064,7 +2361,9 @@ impl Game {
             } else {
                 (None, None)
             };
-            if matches!(king_in_check, Some(CheckType::Double)) && piece.type_() != PieceType::King
+            if !pseudo_legal
+                && matches!(king_in_check, Some(CheckType::Double))
+                && piece.type_() != PieceType::King
             {
                 continue;
             }
@@ -1153,7 +2452,9 @@ impl Game {
                         };
                         let mut temp_board = self.board.clone();
                         temp_board.execute(enpassant.clone());
-                        if temp_board.is_attacked(king.position, self.current_player.opposite()) {
+                        if !pseudo_legal
+                            && temp_board.is_attacked(king.position, self.current_player.opposite())
+                        {
                             continue;
                         }
                         let (direct, discovered) = enemy_king
@@ -1212,7 +2513,8 @@ impl Game {
                         .map(|off| off.wrapping_add(piece.position))
                         .filter(|pos| is_valid_coord(*pos))
                         .filter(|pos| {
-                            !kingless_board.is_attacked(*pos, self.current_player.opposite())
+                            pseudo_legal
+                                || !kingless_board.is_attacked(*pos, self.current_player.opposite())
                         })
                     {
                         let cell = self.board.arr[pos as usize];
@@ -1235,35 +2537,41 @@ impl Game {
                     {
                         continue;
                     }
-                    for (castling_side, piece_flag) in zip(
-                        [CastlingSide::KingSide, CastlingSide::QueenSide],
-                        [PieceFlag::CanCastleKingSide, PieceFlag::CanCastleQueenSide],
-                    ) {
-                        let rook_pos = piece.position & 0xf0 | castling_side as u8;
-                        let cell = self.board.arr[rook_pos as usize];
-                        if !PieceFlag::Moved.is_set(cell)
-                            && Color::from_byte(cell) == self.current_player
-                            && PieceType::from_byte(cell) == PieceType::Rook
-                            && piece_flag.is_set(piece.code)
-                            && between(rook_pos, piece.position)
-                                .map(|pos| (self.board.arr[pos as usize], pos))
-                                .enumerate()
-                                .all(|(i, (code, pos))| {
-                                    code == 0x00
-                                        && ((castling_side == CastlingSide::QueenSide && i == 0)
-                                            || !self
-                                                .board
-                                                .is_attacked(pos, self.current_player.opposite()))
-                                })
-                        {
-                            possible_moves.push(Move {
-                                piece,
-                                move_type: MoveType::Castling(
-                                    castling_side,
-                                    Piece::from_code(cell, rook_pos),
-                                ),
-                                check: CheckType::None,
+                    let span = |from: u8, to: u8| from.min(to)..=from.max(to);
+                    for castling_side in [CastlingSide::KingSide, CastlingSide::QueenSide] {
+                        let Some(rook) =
+                            self.board.castling_rook(self.current_player, castling_side)
+                        else {
+                            continue;
+                        };
+                        let castling = Move {
+                            piece,
+                            move_type: MoveType::Castling(castling_side, rook),
+                            check: CheckType::None,
+                        };
+                        let (king_to, rook_to) =
+                            (castling.end_position(), castling.castling_rook_position());
+                        // Squares both pieces pass, apart from their own, have to be empty
+                        let path_is_empty = span(piece.position, king_to)
+                            .chain(span(rook.position, rook_to))
+                            .all(|pos| {
+                                pos == piece.position
+                                    || pos == rook.position
+                                    || self.board.arr[pos as usize] == 0x00
+                            });
+                        if !path_is_empty {
+                            continue;
+                        }
+                        // In Chess960 castling rook may hide an attacker of the king's path
+                        let mut rookless_board = self.board.clone();
+                        rookless_board.arr[rook.position()] = 0x00;
+                        if span(piece.position, king_to)
+                            .filter(|pos| *pos != piece.position)
+                            .all(|pos| {
+                                !rookless_board.is_attacked(pos, self.current_player.opposite())
                             })
+                        {
+                            possible_moves.push(castling);
                         }
                     }
                 }
@@ -1308,38 +2616,8 @@ impl Game {
                 }
             }
         }
-        if matches!(
-            king_in_check,
-            Some(CheckType::Direct) | Some(CheckType::Discovered)
-        ) {
-            let attack_pieces = self
-                .board
-                .who_can_attack(king)
-                .expect("Incorrect check: attacker is not found");
-            assert!(
-                attack_pieces.len() == 1,
-                "There can be only one piece to attack the king"
-            );
-            let attacker = attack_pieces.into_iter().next().unwrap();
-            let possible_positions: Vec<_> = if matches!(
-                attacker.type_(),
-                PieceType::Bishop | PieceType::Queen | PieceType::Rook
-            ) {
-                between(attacker.position, king.position).collect()
-            } else {
-                Vec::new()
-            };
-            possible_moves = possible_moves
-                .into_iter()
-                .filter(|_move| match _move.move_type() {
-                    _ if _move.piece().type_() == PieceType::King => true,
-                    MoveType::EnPassantCapture(pawn, _) if pawn == &attacker => true,
-                    _ => {
-                        _move.end_position() == attacker.position
-                            || possible_positions.contains(&_move.end_position())
-                    }
-                })
-                .collect();
+        if check_block.is_some() {
+            possible_moves.retain(resolves_check);
         }
         if bot {
             for idx in 0..possible_moves.len() {
@@ -1410,6 +2688,27 @@ impl Game {
     }
 
     pub fn execute(&mut self, _move: Move) -> Option<GameEndState> {
+        self.moves_cache = OnceLock::new();
+        self.end_state = self.play_move(_move);
+        self.end_state
+    }
+
+    fn play_move(&mut self, _move: Move) -> Option<GameEndState> {
+        self.clock_history.push(self.halfmove_clock);
+        if _move.piece().type_() == PieceType::Pawn
+            || matches!(_move.move_type(), MoveType::Capture(_))
+        {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if self.current_player == Color::Black {
+            self.fullmove_number += 1;
+        }
+        if let Some(clock) = self.clock.as_mut() {
+            self.clock_states.push(*clock);
+            clock.add_increment(self.current_player);
+        }
         self.current_player = self.current_player.opposite();
         self.board.execute(_move.clone());
         // Check to see if move caused direct check!
@@ -1447,17 +2746,33 @@ impl Game {
         } else {
             _move
         };
-        self.history.record(_move.clone());
-        let compressed_board = self.board.compress();
-        match _move.move_type() {
-            MoveType::QuietMove(_) => {
-                if self.existed_positions.count(&compressed_board) >= 2 {
-                    return Some(GameEndState::DrawThreefoldRepetition);
+        let _move = match self.mode {
+            GameMode::Atomic if _move.is_capture() => {
+                let exploded = self.board.explode(_move.end_position());
+                self.explosions.push(exploded);
+                // Capturing piece is gone, so only discovered checks are left
+                Move {
+                    check: self.board.atomic_check(self.current_player),
+                    .._move
                 }
             }
-            _ => self.existed_positions.clear(),
+            GameMode::Atomic if self.board.kings_touching() => Move {
+                check: CheckType::None,
+                .._move
+            },
+            _ => _move,
+        };
+        self.history.record(_move.clone());
+        if _move.check() != CheckType::None {
+            self.check_counts[self.current_player.opposite() as usize >> 7] += 1;
+        }
+        let position_key = self.position_key();
+        let threefold_repetition = self.existed_positions.count(position_key) >= 2;
+        self.existed_positions.push(position_key);
+        // Exploded king can't be looked for checks
+        if let Some(end_state) = self.variant_end(&_move) {
+            return Some(end_state);
         }
-        self.existed_positions.push(compressed_board);
         #[cfg(debug_assertions)]
         {
             let current_check = self.current_check_state();
@@ -1470,30 +2785,526 @@ impl Game {
                 panic!("Got different check type current: {current_check:?} from move: {check_from_move:?}");
             }
         }
-        if self.get_possible_moves(false).is_empty() {
+        // Bare king can still win King of the Hill
+        if self.mode != GameMode::KingOfTheHill && self.board.is_dead_position() {
+            return Some(GameEndState::DrawInsufficientMaterial);
+        }
+        if threefold_repetition {
+            return Some(GameEndState::DrawThreefoldRepetition);
+        }
+        if self.first_legal_move().is_none() {
             match _move.check {
                 CheckType::None => Some(GameEndState::DrawStalemate),
                 _ => Some(GameEndState::CheckMate(self.current_player.opposite())),
             }
+        } else if self.halfmove_clock >= 100 {
+            Some(GameEndState::DrawFiftyMoveRule)
+        } else {
+            None
+        }
+    }
+
+    /** Win by variant rules after the move, if any */
+    fn variant_end(&self, last_move: &Move) -> Option<GameEndState> {
+        let mover = self.current_player.opposite();
+        match self.mode {
+            GameMode::Standard => None,
+            GameMode::KingOfTheHill => (last_move.piece().type_() == PieceType::King
+                && center_distance(last_move.end_position()) == 0)
+                .then_some(GameEndState::VariantWin(mover)),
+            GameMode::ThreeCheck => (self.check_counts[mover as usize >> 7] >= 3)
+                .then_some(GameEndState::ThreeCheckWin(mover)),
+            GameMode::Atomic => (!self.board.has_king(self.current_player))
+                .then_some(GameEndState::VariantWin(mover)),
+        }
+    }
+
+    /** How many times current position occurred during the game */
+    fn repetitions(&self) -> usize {
+        self.existed_positions.count(self.position_key())
+    }
+
+    /** Draw player may claim now: threefold repetition or fifty moves without progress */
+    pub fn claimable_draw(&self) -> Option<GameEndState> {
+        if self.repetitions() >= 3 {
+            Some(GameEndState::DrawThreefoldRepetition)
+        } else if self.halfmove_clock >= 100 {
+            Some(GameEndState::DrawFiftyMoveRule)
+        } else {
+            None
+        }
+    }
+
+    pub fn can_claim_draw(&self) -> bool {
+        self.claimable_draw().is_some()
+    }
+
+    /** Draw ending game without a claim: fivefold repetition or seventy five moves
+     * without progress, reported as repetition and fifty-move draws. */
+    pub fn automatic_draw(&self) -> Option<GameEndState> {
+        if self.repetitions() >= 5 {
+            Some(GameEndState::DrawThreefoldRepetition)
+        } else if self.halfmove_clock >= 150 {
+            Some(GameEndState::DrawFiftyMoveRule)
         } else {
             None
         }
     }
 
+    /** Is player to move in check */
+    pub fn in_check(&self) -> bool {
+        self.history
+            .last_move()
+            .is_some_and(|_move| _move.check() != CheckType::None)
+    }
+
+    /** How the game ended: the recorded end state when there is one, like a variant win,
+     * flag fall or agreed draw, otherwise judging by the board only. Draws by repetition
+     * or fifty-move rule aren't detected from the board. */
+    pub fn outcome(&self) -> Option<GameEndState> {
+        if self.end_state.is_some() {
+            self.end_state
+        } else if self.legal_moves_cached().is_empty() {
+            Some(if self.in_check() {
+                GameEndState::CheckMate(self.current_player.opposite())
+            } else {
+                GameEndState::DrawStalemate
+            })
+        } else if self.board.is_dead_position() {
+            Some(GameEndState::DrawInsufficientMaterial)
+        } else {
+            None
+        }
+    }
+
+    pub fn snapshot(&self) -> PositionSnapshot {
+        PositionSnapshot {
+            fen: self.to_fen(),
+            side_to_move: self.current_player,
+            in_check: self.in_check(),
+            legal_uci: self
+                .legal_moves_expanded()
+                .iter()
+                .map(Move::to_uci)
+                .collect(),
+            outcome: self.outcome(),
+        }
+    }
+
+    /** Position is quiet when side to move isn't in check and has no winning captures. */
+    pub fn is_quiet(&self) -> bool {
+        !self.in_check()
+            && self
+                .legal_moves()
+                .iter()
+                .all(|_move| self.board.static_exchange(_move) <= 0)
+    }
+
+    /** Legal moves by rules of the game mode for human players, one move per
+     * promotion square. Promotion piece is left to be picked with `set_promotion_type`
+     * before the move is executed. */
+    pub fn legal_moves(&self) -> Vec<Move> {
+        self.moves_by_rules(false, false)
+    }
+
+    /** Legal moves by rules of the game mode, with promotions to every piece.
+     * That's what search, perft and move parsing need. */
+    pub fn legal_moves_expanded(&self) -> Vec<Move> {
+        self.moves_by_rules(true, false)
+    }
+
+    /** Moves by standard rules, variants changing legality hook in here.
+     * With `first_only` a short non-empty prefix is enough. */
+    fn moves_by_rules(&self, expand_promotions: bool, first_only: bool) -> Vec<Move> {
+        match self.mode {
+            GameMode::Standard | GameMode::KingOfTheHill | GameMode::ThreeCheck => {
+                self.generate_moves(expand_promotions, first_only, false)
+            }
+            GameMode::Atomic => {
+                // Game is over once a king is exploded
+                if !self.board.has_king(self.current_player) {
+                    return Vec::new();
+                }
+                let legal = self
+                    .generate_moves(expand_promotions, false, true)
+                    .into_iter()
+                    .filter_map(|_move| self.atomic_legal(_move));
+                if first_only {
+                    legal.take(1).collect()
+                } else {
+                    legal.collect()
+                }
+            }
+        }
+    }
+
+    /** Pseudo-legal move checked by Atomic rules, with check it gives after explosion.
+     *
+     * Kings can't capture and own king has to survive the move. Exploding enemy king
+     * wins outright, otherwise own king can't be left in check. */
+    fn atomic_legal(&self, _move: Move) -> Option<Move> {
+        if _move.is_capture() && _move.piece().type_() == PieceType::King {
+            return None;
+        }
+        let mut board = self.board.clone();
+        board.execute(_move.clone());
+        if _move.is_capture() {
+            board.explode(_move.end_position());
+        }
+        let opponent = self.current_player.opposite();
+        if !board.has_king(self.current_player) {
+            return None;
+        }
+        if !board.has_king(opponent) {
+            return Some(_move);
+        }
+        if board.atomic_check(self.current_player) != CheckType::None {
+            return None;
+        }
+        let check = if _move.is_capture() {
+            board.atomic_check(opponent)
+        } else if board.kings_touching() {
+            CheckType::None
+        } else {
+            _move.check
+        };
+        Some(Move { check, .._move })
+    }
+
+    /** Same as `legal_moves_expanded`, but malformed positions, which would make
+     * generator panic, are reported as error instead. */
+    pub fn try_legal_moves(&self) -> Result<Vec<Move>, String> {
+        if let Some(piece) = self
+            .board
+            .iter_pieces()
+            .find(|piece| piece.type_() == PieceType::Invalid)
+        {
+            return Err(format!("Invalid piece on {}", pos_to_str(piece.position)));
+        }
+        self.validate()?;
+        let king = self
+            .board
+            .iter_pieces()
+            .find(|piece| piece.color() == self.current_player && piece.type_() == PieceType::King)
+            .expect("Validated position has a king");
+        let checkers = self
+            .board
+            .who_can_attack(king)
+            .map_or(0, |attackers| attackers.len());
+        let recorded = self
+            .history
+            .last_move()
+            .map_or(CheckType::None, |_move| _move.check());
+        let expected = match recorded {
+            CheckType::None => 0,
+            CheckType::Direct | CheckType::Discovered => 1,
+            CheckType::Double => 2,
+        };
+        if checkers != expected {
+            return Err(format!(
+                "{} is attacked by {checkers} pieces, but last move has {recorded:?} check",
+                self.current_player
+            ));
+        }
+        Ok(self.legal_moves_expanded())
+    }
+
+    /** Same moves as `legal_moves`, generated once per position */
+    pub fn legal_moves_cached(&self) -> &[Move] {
+        self.moves_cache.get_or_init(|| self.legal_moves())
+    }
+
+    /** Legal promotions of the pawn on `from`, quiet and captures, to every piece */
+    pub fn promotion_moves_from(&self, from: u8) -> Vec<Move> {
+        self.legal_moves_expanded()
+            .into_iter()
+            .filter(|_move| {
+                _move.piece().position == from
+                    && matches!(
+                        _move.move_type(),
+                        MoveType::PromotionQuiet(..) | MoveType::PromotionCapture(..)
+                    )
+            })
+            .collect()
+    }
+
+    /** Pattern of checkmate on the board, `None` if current player isn't mated */
+    pub fn mate_pattern(&self) -> Option<MatePattern> {
+        if !matches!(self.outcome(), Some(GameEndState::CheckMate(_))) {
+            return None;
+        }
+        let king = self
+            .board
+            .iter_pieces()
+            .find(|piece| piece.color() == self.current_player && piece.type_() == PieceType::King)
+            .expect("Mated player has a king");
+        let attackers = self.board.who_can_attack(king).unwrap_or_default();
+        let [attacker] = attackers.as_slice() else {
+            return Some(MatePattern::Other);
+        };
+        let blocked_by_own = |pos: &u8| {
+            let cell = self.board.arr[*pos as usize];
+            cell != 0x00 && Color::from_byte(cell) == self.current_player
+        };
+        let neighbours: Vec<u8> = KING_MOVES
+            .iter()
+            .map(|offset| offset.wrapping_add(king.position))
+            .filter(|pos| is_valid_coord(*pos))
+            .collect();
+        let back_row = castling_row(self.current_player);
+        Some(match attacker.type_() {
+            PieceType::Knight if neighbours.iter().all(blocked_by_own) => MatePattern::Smothered,
+            PieceType::Rook | PieceType::Queen
+                if king.position & 0xf0 == back_row
+                    && attacker.position & 0xf0 == back_row
+                    && neighbours
+                        .iter()
+                        .filter(|pos| *pos & 0xf0 != back_row)
+                        .all(blocked_by_own) =>
+            {
+                MatePattern::BackRank
+            }
+            _ => MatePattern::Other,
+        })
+    }
+
+    /** Legal castling moves of the current player, king side first */
+    pub fn castling_moves(&self) -> Vec<Move> {
+        self.legal_moves_expanded()
+            .into_iter()
+            .filter(|_move| matches!(_move.move_type(), MoveType::Castling(..)))
+            .collect()
+    }
+
+    /** Possible moves grouped by the moving piece, in order of generation */
+    pub fn moves_by_piece(&self) -> Vec<(Piece, Vec<Move>)> {
+        let mut groups: Vec<(Piece, Vec<Move>)> = Vec::new();
+        for _move in self.legal_moves_expanded() {
+            match groups.iter_mut().find(|(piece, _)| piece == _move.piece()) {
+                Some((_, moves)) => moves.push(_move),
+                None => groups.push((*_move.piece(), vec![_move])),
+            }
+        }
+        groups
+    }
+
+    /** Disambiguation SAN needs for the move, judging by other pieces of the same type
+     * which can legally reach the same square. Pawns never need one. */
+    pub fn disambiguation(&self, _move: &Move) -> Disambiguation {
+        let piece = _move.piece();
+        if piece.type_() == PieceType::Pawn {
+            return Disambiguation::None;
+        }
+        let rivals: Vec<_> = self
+            .legal_moves()
+            .into_iter()
+            .filter(|other| {
+                other.piece() != piece
+                    && other.piece().type_() == piece.type_()
+                    && other.end_position() == _move.end_position()
+            })
+            .map(|other| other.piece().position)
+            .collect();
+        if rivals.is_empty() {
+            Disambiguation::None
+        } else if rivals.iter().all(|pos| pos & 0x0f != piece.position & 0x0f) {
+            Disambiguation::File
+        } else if rivals.iter().all(|pos| pos & 0xf0 != piece.position & 0xf0) {
+            Disambiguation::Rank
+        } else {
+            Disambiguation::Both
+        }
+    }
+
+    /** Type and color of the piece captured by the last move */
+    pub fn last_capture(&self) -> Option<(PieceType, Color)> {
+        match self.history.last_move()?.move_type() {
+            MoveType::Capture(victim)
+            | MoveType::PromotionCapture(victim, _)
+            | MoveType::EnPassantCapture(victim, _) => Some((victim.type_(), victim.color())),
+            _ => None,
+        }
+    }
+
+    /** Would the move leave opponent without moves and out of check */
+    pub fn would_stalemate(&self, _move: &Move) -> bool {
+        if _move.check() != CheckType::None {
+            return false;
+        }
+        let mut next = self.clone();
+        matches!(
+            next.execute(_move.clone()),
+            Some(GameEndState::DrawStalemate)
+        )
+    }
+
+    /** Move in Standard Algebraic Notation, with check and mate glyphs */
+    pub fn to_san(&self, _move: &Move) -> String {
+        self.light_clone().execute_san(_move.clone()).0
+    }
+
+    /** Executes the move, returning it in SAN with glyphs and the end state it led to.
+     * Replays use it so glyphs come from the move they make anyway. */
+    pub(crate) fn execute_san(&mut self, _move: Move) -> (String, Option<GameEndState>) {
+        let piece = _move.piece();
+        let from = pos_to_str(piece.position);
+        let mut san = match _move.move_type() {
+            MoveType::Castling(CastlingSide::KingSide, _) => "O-O".to_string(),
+            MoveType::Castling(CastlingSide::QueenSide, _) => "O-O-O".to_string(),
+            move_type => {
+                let mut san = String::with_capacity(8);
+                if piece.type_() == PieceType::Pawn {
+                    if _move.is_capture() {
+                        san.push_str(&from[..1]);
+                    }
+                } else {
+                    san.push(piece.type_().letter().to_ascii_uppercase());
+                    match self.disambiguation(&_move) {
+                        Disambiguation::None => {}
+                        Disambiguation::File => san.push_str(&from[..1]),
+                        Disambiguation::Rank => san.push_str(&from[1..]),
+                        Disambiguation::Both => san.push_str(from),
+                    }
+                }
+                if _move.is_capture() {
+                    san.push('x');
+                }
+                san.push_str(pos_to_str(_move.end_position()));
+                if let MoveType::PromotionQuiet(_, new_type)
+                | MoveType::PromotionCapture(_, new_type) = move_type
+                {
+                    san.push('=');
+                    san.push(new_type.letter().to_ascii_uppercase());
+                }
+                san
+            }
+        };
+        let end_state = self.execute(_move);
+        if let Some(GameEndState::CheckMate(_)) = end_state {
+            san.push('#');
+        } else if self
+            .history
+            .last_move()
+            .is_some_and(|_move| _move.check() != CheckType::None)
+        {
+            san.push('+');
+        }
+        (san, end_state)
+    }
+
+    /** Game before the first played move and played moves in order */
+    pub(crate) fn replay_start(&self) -> (Game, Vec<Move>) {
+        let mut game = self.clone();
+        let mut moves = Vec::new();
+        while let Some(last_move) = game.history.last_move() {
+            if last_move.is_synthetic() {
+                break;
+            }
+            game.undo_last_move()
+                .expect("Recorded move should be possible to undo");
+            moves.push(last_move);
+        }
+        moves.reverse();
+        (game, moves)
+    }
+
+    /** Played moves with everything move list needs to show them */
+    pub fn annotated_history(&self) -> Vec<MoveInfo> {
+        let (mut game, moves) = self.replay_start();
+        moves
+            .into_iter()
+            .enumerate()
+            .map(|(ply, _move)| {
+                let is_capture = _move.is_capture();
+                let is_check = _move.check() != CheckType::None;
+                let (san, end_state) = game.execute_san(_move);
+                let is_mate = matches!(end_state, Some(GameEndState::CheckMate(_)));
+                MoveInfo {
+                    san,
+                    is_check,
+                    is_mate,
+                    is_capture,
+                    ply,
+                }
+            })
+            .collect()
+    }
+
+    /** End state of the last `execute`, flag fall or `finish`, so clients can tell why
+     * game ended. It's `None` after undo and for games set up from FEN or synced board. */
+    pub fn last_end_state(&self) -> Option<GameEndState> {
+        self.end_state
+    }
+
+    /** Moves giving check to the opponent */
+    pub fn checking_moves(&self) -> Vec<Move> {
+        self.legal_moves_expanded()
+            .into_iter()
+            .filter(|_move| _move.check() != CheckType::None)
+            .collect()
+    }
+
+    /** First move which checkmates the opponent right away, if there is one */
+    pub fn mate_in_one(&self) -> Option<Move> {
+        self.checking_moves().into_iter().find(|_move| {
+            let mut next = self.clone();
+            matches!(
+                next.execute(_move.clone()),
+                Some(GameEndState::CheckMate(_))
+            )
+        })
+    }
+
+    /** Plain sum of piece values for the current player minus the opponent's.
+     *
+     * Handy for debugging, as it's linear and symmetric between colors. */
+    pub fn material_only_eval(&self) -> i32 {
+        match self.current_player {
+            Color::White => self.board.material_balance(),
+            Color::Black => -self.board.material_balance(),
+        }
+    }
+
     pub fn undo_last_move(&mut self) -> Result<(), &'static str> {
         let last_move = self
             .history
             .last_move()
             .ok_or_else(|| "There's no move to undo.")?;
-        self.existed_positions.undo_move(*last_move.move_type());
+        let gave_check = last_move.check() != CheckType::None;
+        self.moves_cache = OnceLock::new();
+        self.end_state = None;
+        self.existed_positions.undo_move();
+        if self.mode == GameMode::Atomic && last_move.is_capture() {
+            // Capturing piece is put back too, then undo moves it home
+            for piece in self.explosions.pop().unwrap_or_default() {
+                self.board.arr[piece.position()] = piece.code;
+            }
+        }
         self.board.undo(last_move);
         self.history.unrecord();
         self.current_player = self.current_player.opposite();
+        if gave_check {
+            // Checks set up by FEN weren't counted
+            let count = &mut self.check_counts[self.current_player as usize >> 7];
+            *count = count.saturating_sub(1);
+        }
+        if let Some(halfmove_clock) = self.clock_history.pop() {
+            self.halfmove_clock = halfmove_clock;
+        }
+        if let Some(clock) = self.clock_states.pop() {
+            self.clock = Some(clock);
+        }
+        if self.current_player == Color::Black {
+            self.fullmove_number -= 1;
+        }
         Ok(())
     }
 
     #[cfg(debug_assertions)]
     fn current_check_state(&self) -> CheckType {
+        if self.mode == GameMode::Atomic && self.board.kings_touching() {
+            return CheckType::None;
+        }
         let king =
             if let Some(king) = self.board.iter_pieces().find(|piece| {
                 piece.color() == self.current_player && piece.type_() == PieceType::King
@@ -1543,19 +3354,330 @@ impl Game {
         }
     }
 
+    /** Is the move possible in current position, check annotation isn't compared */
+    pub fn is_legal(&self, _move: &Move) -> bool {
+        self.find_possible_move(_move).is_some()
+    }
+
+    /** Possible move matching given one, check annotation isn't compared */
+    pub fn find_possible_move(&self, _move: &Move) -> Option<Move> {
+        self.legal_moves_expanded().into_iter().find(|possible| {
+            possible.piece() == _move.piece() && possible.move_type() == _move.move_type()
+        })
+    }
+
+    /** Possible move written in UCI notation, promotion defaults to queen */
+    pub fn parse_uci(&self, uci: &str) -> Result<Move, String> {
+        let square = |range| {
+            uci.get(range)
+                .and_then(str_to_pos)
+                .ok_or_else(|| format!("Malformed UCI move '{uci}'"))
+        };
+        let (from, to) = (square(0..2)?, square(2..4)?);
+        match uci.get(4..) {
+            Some("") => self.build_move(from, to, None).map_err(|err| {
+                match self.build_move(from, to, Some(PieceType::Queen)) {
+                    Ok(_) => format!("Promotion piece missing in UCI move '{uci}'"),
+                    Err(_) => err,
+                }
+            }),
+            Some(letter) => {
+                let promotion = letter
+                    .parse::<char>()
+                    .ok()
+                    .and_then(|letter| PieceType::try_from(letter).ok())
+                    .filter(|new_type| !matches!(new_type, PieceType::Pawn | PieceType::King))
+                    .ok_or_else(|| format!("Unknown promotion '{letter}' in UCI move '{uci}'"))?;
+                self.build_move(from, to, Some(promotion))
+            }
+            None => Err(format!("Malformed UCI move '{uci}'")),
+        }
+    }
+
+    /** Possible move written in SAN, check and annotation glyphs are ignored */
+    pub fn parse_san(&self, san: &str) -> Result<Move, String> {
+        let malformed = || format!("Malformed SAN move '{san}'");
+        let body = san.trim_end_matches(['+', '#', '!', '?']);
+        let castling = match body {
+            "O-O" | "0-0" => Some(CastlingSide::KingSide),
+            "O-O-O" | "0-0-0" => Some(CastlingSide::QueenSide),
+            _ => None,
+        };
+        if let Some(side) = castling {
+            return self
+                .legal_moves_expanded()
+                .into_iter()
+                .find(|_move| matches!(_move.move_type(), MoveType::Castling(s, _) if *s == side))
+                .ok_or_else(|| format!("Castling '{san}' isn't possible"));
+        }
+        let (body, promotion) = match body.split_once('=') {
+            Some((body, letter)) => {
+                let promotion = letter
+                    .parse::<char>()
+                    .ok()
+                    .and_then(|letter| PieceType::try_from(letter).ok())
+                    .filter(|new_type| !matches!(new_type, PieceType::Pawn | PieceType::King))
+                    .ok_or_else(|| format!("Unknown promotion '{letter}' in SAN move '{san}'"))?;
+                (body, Some(promotion))
+            }
+            None => (body, None),
+        };
+        let (piece_type, body) = match body.chars().next() {
+            Some(letter) if letter.is_ascii_uppercase() => (
+                PieceType::try_from(letter).map_err(|_| malformed())?,
+                &body[1..],
+            ),
+            _ => (PieceType::Pawn, body),
+        };
+        let split = body.len().checked_sub(2).ok_or_else(malformed)?;
+        let to = body
+            .get(split..)
+            .and_then(str_to_pos)
+            .ok_or_else(malformed)?;
+        let hint = body[..split].trim_end_matches('x');
+        if hint.len() > 2 || !hint.chars().all(|c| matches!(c, 'a'..='h' | '1'..='8')) {
+            return Err(malformed());
+        }
+        let mut candidates = self.legal_moves_expanded().into_iter().filter(|_move| {
+            let from = pos_to_str(_move.piece().position);
+            let new_type = match _move.move_type() {
+                MoveType::PromotionQuiet(_, new_type) | MoveType::PromotionCapture(_, new_type) => {
+                    Some(*new_type)
+                }
+                _ => None,
+            };
+            _move.piece().type_() == piece_type
+                && !matches!(_move.move_type(), MoveType::Castling(..))
+                && _move.end_position() == to
+                && new_type == promotion
+                && hint.chars().all(|c| from.contains(c))
+        });
+        match (candidates.next(), candidates.next()) {
+            (Some(_move), None) => Ok(_move),
+            (Some(_), Some(_)) => Err(format!("SAN move '{san}' is ambiguous")),
+            (None, _) => Err(format!("No possible move matches SAN '{san}'")),
+        }
+    }
+
+    /** Possible move between two squares with correct move type filled in.
+     *
+     * Promotion piece is required for promotions and forbidden otherwise. */
+    pub fn build_move(
+        &self,
+        from: u8,
+        to: u8,
+        promotion: Option<PieceType>,
+    ) -> Result<Move, String> {
+        let mut candidates = self
+            .legal_moves_expanded()
+            .into_iter()
+            .filter(|_move| _move.piece().position == from && _move.uci_end_position() == to)
+            .peekable();
+        if candidates.peek().is_none() {
+            return Err(format!(
+                "No possible move from {} to {}",
+                pos_to_str(from),
+                pos_to_str(to)
+            ));
+        }
+        candidates
+            .find(|_move| match _move.move_type() {
+                MoveType::PromotionQuiet(_, new_type) | MoveType::PromotionCapture(_, new_type) => {
+                    Some(*new_type) == promotion
+                }
+                _ => promotion.is_none(),
+            })
+            .ok_or_else(|| match promotion {
+                Some(new_type) => format!(
+                    "Move from {} to {} can't promote to {new_type:?}",
+                    pos_to_str(from),
+                    pos_to_str(to)
+                ),
+                None => format!(
+                    "Move from {} to {} needs promotion piece",
+                    pos_to_str(from),
+                    pos_to_str(to)
+                ),
+            })
+    }
+
+    /** Played moves in UCI notation, only last one is known without full history */
+    pub fn to_uci_moves(&self) -> Vec<String> {
+        let moves = match &self.history {
+            GameHistory::FullHistory(moves) => moves.clone(),
+            GameHistory::LastMove(last_move) => last_move.iter().cloned().collect(),
+        };
+        moves
+            .iter()
+            .filter(|_move| !_move.is_synthetic())
+            .map(Move::to_uci)
+            .collect()
+    }
+
+    /** Game from the initial position with moves in UCI notation played */
+    pub fn from_uci_moves<S: AsRef<str>>(moves: &[S]) -> Result<Game, MoveError> {
+        let mut game = Game::from_fen(STARTING_FEN).expect("Starting position should be valid");
+        game.apply_uci_line(moves)?;
+        Ok(game)
+    }
+
+    /** Executes moves in UCI notation one by one, see `play_line` */
+    pub fn apply_uci_line<S: AsRef<str>>(
+        &mut self,
+        moves: &[S],
+    ) -> Result<Option<GameEndState>, MoveError> {
+        self.apply_notation_line(moves, Game::parse_uci)
+    }
+
+    /** Executes moves in SAN one by one, see `play_line` */
+    pub fn apply_san_line<S: AsRef<str>>(
+        &mut self,
+        moves: &[S],
+    ) -> Result<Option<GameEndState>, MoveError> {
+        self.apply_notation_line(moves, Game::parse_san)
+    }
+
+    fn apply_notation_line<S: AsRef<str>>(
+        &mut self,
+        moves: &[S],
+        parse: fn(&Game, &str) -> Result<Move, String>,
+    ) -> Result<Option<GameEndState>, MoveError> {
+        let mut end_state = None;
+        for (index, notation) in moves.iter().enumerate() {
+            if end_state.is_some() {
+                return Err(MoveError::GameEnded(index));
+            }
+            let _move = parse(self, notation.as_ref()).map_err(|_| MoveError::Illegal(index))?;
+            end_state = self.execute(_move);
+        }
+        Ok(end_state)
+    }
+
+    /** Executes moves one by one, returning end state after the last of them.
+     *
+     * Stops at the first move which isn't possible, moves before it stay executed. */
+    pub fn play_line(&mut self, moves: &[Move]) -> Result<Option<GameEndState>, MoveError> {
+        let mut end_state = None;
+        for (index, _move) in moves.iter().enumerate() {
+            if end_state.is_some() {
+                return Err(MoveError::GameEnded(index));
+            }
+            let _move = self
+                .find_possible_move(_move)
+                .ok_or(MoveError::Illegal(index))?;
+            end_state = self.execute(_move);
+        }
+        Ok(end_state)
+    }
+
+    /** Brings game to the state received from server, keeping local history when possible.
+     *
+     * Sync either matches local position or is one `last_move` ahead of it,
+     * anything else is a desync and game is rebuilt from the synced state.
+     * Returns `true` if game was rebuilt. */
+    pub fn resume_from_sync(
+        &mut self,
+        board: Board,
+        last_move: Option<Move>,
+        current_player: Color,
+    ) -> bool {
+        if self.current_player == current_player && self.board.arr == board.arr {
+            return false;
+        }
+        if let Some(synced_move) = last_move.as_ref() {
+            if let Some(local_move) = self.find_possible_move(synced_move) {
+                let mut next = self.clone();
+                next.execute(local_move);
+                if next.current_player == current_player && next.board.arr == board.arr {
+                    *self = next;
+                    return false;
+                }
+            }
+        }
+        log::warn!("Game desync detected, rebuilding game from server state");
+        *self = Game::new(board, current_player, last_move);
+        true
+    }
+
+    /** Copy without past moves, for search to execute and undo moves on it */
     pub fn light_clone(&self) -> Self {
         Self {
             board: self.board.clone(),
             current_player: self.current_player,
             existed_positions: self.existed_positions.clone(),
             history: self.history.light_clone(),
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            clock_history: self.clock_history.clone(),
+            mode: self.mode,
+            check_counts: self.check_counts,
+            clock: self.clock,
+            clock_states: self.clock_states.clone(),
+            explosions: self.explosions.clone(),
+            end_state: self.end_state,
+            moves_cache: self.moves_cache.clone(),
         }
     }
 
+    /** Same game played by rules of given mode */
+    pub fn with_mode(mut self, mode: GameMode) -> Game {
+        self.mode = mode;
+        self
+    }
+
+    pub fn mode(&self) -> GameMode {
+        self.mode
+    }
+
+    /** Same game played with given clock */
+    pub fn with_clock(mut self, clock: Clock) -> Game {
+        self.clock = Some(clock);
+        self
+    }
+
+    pub fn clock(&self) -> Option<&Clock> {
+        self.clock.as_ref()
+    }
+
+    /** Charges elapsed time to the player to move.
+     *
+     * When their flag falls opponent wins, unless they can't checkmate at all. */
+    pub fn tick(&mut self, elapsed: Duration) -> Option<GameEndState> {
+        let clock = self.clock.as_mut()?;
+        if !clock.charge(self.current_player, elapsed) {
+            return None;
+        }
+        let opponent = self.current_player.opposite();
+        self.end_state = Some(if self.board.has_mating_material(opponent) {
+            GameEndState::TimeForfeit(opponent)
+        } else {
+            GameEndState::DrawInsufficientMaterial
+        });
+        self.end_state
+    }
+
+    /** Ends the game with result decided off the board, like resignation or agreed draw */
+    pub fn finish(&mut self, end_state: GameEndState) {
+        self.end_state = Some(end_state);
+    }
+
+    /** Checks delivered by White and Black during the game */
+    pub fn check_counts(&self) -> (u8, u8) {
+        (self.check_counts[1], self.check_counts[0])
+    }
+
     pub fn current_player(&self) -> Color {
         self.current_player
     }
 
+    pub fn halfmove_clock(&self) -> u16 {
+        self.halfmove_clock
+    }
+
+    pub fn fullmove_number(&self) -> u16 {
+        self.fullmove_number
+    }
+
     pub fn history(&self) -> GameHistory {
         self.history.clone()
     }
@@ -1563,6 +3685,30 @@ impl Game {
     pub fn board(&self) -> &Board {
         &self.board
     }
+
+    /** Board as seen by given player in fog of war.
+     *
+     * View is only for display, legal moves are still found on the real board,
+     * so hidden pins and checks are respected. */
+    pub fn vision(&self, point_of_view: Color) -> Board {
+        self.board
+            .clone()
+            .hide_and_obstruct(point_of_view, self.en_passant_pawn())
+    }
+
+    /** Debugging aid for repetition draws.
+     *
+     * Lists every distinct position key of the game with the number
+     * of times it occurred, in order of first appearance. */
+    pub fn repetition_debug(&self) -> Vec<(u64, usize)> {
+        self.existed_positions.occurrences()
+    }
+
+    /** Position keys after every executed ply, in order.
+     * Equal keys mark repeated positions. */
+    pub fn hash_history(&self) -> Vec<u64> {
+        self.existed_positions.keys.clone()
+    }
 }
 
 /** Bits structure of piece code
@@ -1648,6 +3794,20 @@ impl Piece {
         self.position as usize
     }
 
+    /** Is piece a slider lined up with target, ignoring anything in between */
+    #[inline]
+    fn slides_towards(&self, target: u8) -> bool {
+        match self.type_() {
+            PieceType::Bishop => is_in_diagonal_line(self.position, target),
+            PieceType::Rook => is_in_straight_line(self.position, target),
+            PieceType::Queen => {
+                is_in_diagonal_line(self.position, target)
+                    || is_in_straight_line(self.position, target)
+            }
+            _ => false,
+        }
+    }
+
     fn can_attack(&self, target: u8, board: [u8; 128]) -> bool {
         // precomputed tables should increase speed of this dramatically
         // No pieces can attack itself
@@ -1676,8 +3836,7 @@ impl Piece {
                         .all(|cell| cell == 0x00)
             }
             PieceType::Queen => {
-                (is_in_straight_line(self.position, target)
-                    || is_in_diagonal_line(self.position, target))
+                line_step(self.position, target) != 0
                     && between(self.position, target)
                         .map(|pos| board[pos as usize])
                         .all(|cell| cell == 0x00)
@@ -1721,6 +3880,15 @@ impl Color {
         unsafe { std::mem::transmute(byte & 0x80) }
     }
 
+    /** Color of FEN/SAN piece letter, uppercase letters are White */
+    pub fn from_fen_char(letter: char) -> Color {
+        if letter.is_uppercase() {
+            Color::White
+        } else {
+            Color::Black
+        }
+    }
+
     pub fn opposite(self) -> Color {
         if self == Color::White {
             Color::Black
@@ -1765,11 +3933,50 @@ pub enum PieceType {
 }
 
 impl PieceType {
+    /** Every real piece type, from pawn to king */
+    pub const ALL: [PieceType; 6] = [
+        PieceType::Pawn,
+        PieceType::Knight,
+        PieceType::Bishop,
+        PieceType::Rook,
+        PieceType::Queen,
+        PieceType::King,
+    ];
+
     #[inline]
     fn from_byte(byte: u8) -> PieceType {
         unsafe { std::mem::transmute(byte & 0x07) }
     }
 
+    /** Material value of the piece in centipawns. */
+    pub fn value(&self) -> i32 {
+        match self {
+            PieceType::Pawn => 100,
+            PieceType::Knight => 325,
+            PieceType::Bishop => 350,
+            PieceType::Rook => 500,
+            PieceType::Queen => 900,
+            PieceType::King => 0,
+            PieceType::Invalid => 0,
+            PieceType::EmptySquare => 0,
+        }
+    }
+
+    /** Lowercase FEN/UCI letter of the piece */
+    fn letter(&self) -> char {
+        match self {
+            PieceType::Pawn => 'p',
+            PieceType::Knight => 'n',
+            PieceType::Bishop => 'b',
+            PieceType::Rook => 'r',
+            PieceType::Queen => 'q',
+            PieceType::King => 'k',
+            PieceType::Invalid | PieceType::EmptySquare => {
+                unreachable!("No letter for empty square")
+            }
+        }
+    }
+
     pub fn is_valid(&self) -> bool {
         matches!(
             self,
@@ -1784,6 +3991,23 @@ impl From<u8> for PieceType {
     }
 }
 
+impl TryFrom<char> for PieceType {
+    type Error = char;
+
+    /** Piece type of FEN/SAN letter in either case, color is up to `Color::from_fen_char` */
+    fn try_from(letter: char) -> Result<Self, Self::Error> {
+        match letter.to_ascii_lowercase() {
+            'p' => Ok(PieceType::Pawn),
+            'n' => Ok(PieceType::Knight),
+            'b' => Ok(PieceType::Bishop),
+            'r' => Ok(PieceType::Rook),
+            'q' => Ok(PieceType::Queen),
+            'k' => Ok(PieceType::King),
+            _ => Err(letter),
+        }
+    }
+}
+
 impl From<PieceType> for u8 {
     fn from(value: PieceType) -> Self {
         value as u8
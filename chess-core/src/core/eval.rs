@@ -0,0 +1,39 @@
+//! Static evaluation of positions for search and advantage display.
+
+use std::cmp::min;
+
+use crate::core::engine::{Board, Color, Piece, PieceType};
+
+/** Material balance for the player in centipawns, capped at 3100.
+ *
+ * Advantage weighs more as material comes off the board and with own pawns count. */
+pub fn evaluate(board: &Board, player: Color) -> i32 {
+    let mut material_difference: i32 = 0;
+    let mut material_total = 0;
+    let mut pawn_advantage = 0;
+    for file in 0..8u8 {
+        for rank in 0..8u8 {
+            let pos = (file << 4) + rank;
+            let piece = Piece::from_code(board.inside()[pos as usize], pos);
+            let material = piece.type_().value();
+            material_total += material;
+            material_difference += if piece.color() == player {
+                material
+            } else {
+                -material
+            };
+            if piece.color() == player && piece.type_() == PieceType::Pawn {
+                pawn_advantage += 1;
+            }
+        }
+    }
+    let ms = min(2400, material_difference.abs())
+        + (material_difference.abs() * pawn_advantage * (8100 - material_total))
+            / (6400 * (pawn_advantage + 1));
+    let total_material_advantage = min(3100, ms);
+    if material_difference >= 0 {
+        total_material_advantage
+    } else {
+        -total_material_advantage
+    }
+}
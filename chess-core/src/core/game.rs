@@ -1,7 +1,7 @@
-use std::cmp::min;
+use std::fmt::Display;
 
 use crate::core::definitions::{Cell, Figure};
-use crate::core::engine::{Board, Color, Piece, PieceFlag, PieceType};
+use crate::core::engine::{fen_letter, Board, Color, Piece, PieceFlag, PieceType};
 use crate::core::utils::compact_pos;
 
 pub fn ui_board(board: &Board) -> Vec<Vec<Cell>> {
@@ -31,43 +31,73 @@ pub fn ui_board(board: &Board) -> Vec<Vec<Cell>> {
         .collect()
 }
 
-#[allow(dead_code)]
-fn material_advantage(board: &Board, player: Color) -> i32 {
-    let mut material_difference: i32 = 0;
-    let mut material_total = 0;
-    let mut pawn_advantage = 0;
-    for file in 0..8u8 {
-        for rank in 0..8u8 {
-            let pos = (file << 4) + rank;
-            let piece = Piece::from_code(board.inside()[pos as usize], pos);
-            let material = match piece.type_() {
-                PieceType::Pawn => 100,
-                PieceType::Knight => 325,
-                PieceType::Bishop => 350,
-                PieceType::Rook => 500,
-                PieceType::Queen => 900,
-                PieceType::King => 0,
-                PieceType::Invalid => 0,
-                PieceType::EmptySquare => 0,
-            };
-            material_total += material;
-            material_difference += if piece.color() == player {
-                material
+/** Board cell `(file, rank)` to draw at display `(row, column)`, counting from top left,
+ * so player sees own pieces at the bottom. Mapping is its own inverse. */
+pub fn display_cell(row: u8, column: u8, point_of_view: Color) -> (u8, u8) {
+    match point_of_view {
+        Color::White => (7 - row, column),
+        Color::Black => (row, 7 - column),
+    }
+}
+
+/** Board drawn with figurine symbols, one line per row, `orientation` side at the bottom.
+ * Empty and unknown squares are drawn as dots on dark squares and spaces on light ones. */
+pub fn unicode_board(board: &Board, orientation: Color) -> String {
+    let mut text = String::with_capacity(8 * 9 * 3);
+    for row in 0..8u8 {
+        for column in 0..8u8 {
+            let (file, rank) = display_cell(row, column, orientation);
+            let piece = board.get(file, rank);
+            text.push(if piece.type_().is_valid() {
+                figurine(piece)
+            } else if (file + rank) % 2 == 0 {
+                '·'
             } else {
-                -material
-            };
-            if piece.color() == player && piece.type_() == PieceType::Pawn {
-                pawn_advantage += 1;
-            }
+                ' '
+            });
+        }
+        text.push('\n');
+    }
+    text
+}
+
+fn figurine(piece: Piece) -> char {
+    match (piece.color(), piece.type_()) {
+        (Color::White, PieceType::King) => '♔',
+        (Color::White, PieceType::Queen) => '♕',
+        (Color::White, PieceType::Rook) => '♖',
+        (Color::White, PieceType::Bishop) => '♗',
+        (Color::White, PieceType::Knight) => '♘',
+        (Color::White, PieceType::Pawn) => '♙',
+        (Color::Black, PieceType::King) => '♚',
+        (Color::Black, PieceType::Queen) => '♛',
+        (Color::Black, PieceType::Rook) => '♜',
+        (Color::Black, PieceType::Bishop) => '♝',
+        (Color::Black, PieceType::Knight) => '♞',
+        (Color::Black, PieceType::Pawn) => '♟',
+        (_, PieceType::Invalid | PieceType::EmptySquare) => {
+            unreachable!("No figurine for empty square")
         }
     }
-    let ms = min(2400, material_difference.abs())
-        + (material_difference.abs() * pawn_advantage * (8100 - material_total))
-            / (6400 * (pawn_advantage + 1));
-    let total_material_advantage = min(3100, ms);
-    if material_difference >= 0 {
-        total_material_advantage
-    } else {
-        -total_material_advantage
+}
+
+impl Display for Board {
+    /** ASCII diagram from rank 8 down to rank 1 with FEN letters, `.` for empty squares */
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in 0..8u8 {
+            write!(f, "{}", 8 - row)?;
+            for column in 0..8u8 {
+                let (file, rank) = display_cell(row, column, Color::White);
+                let piece = self.get(file, rank);
+                let letter = if piece.type_().is_valid() {
+                    fen_letter(piece)
+                } else {
+                    '.'
+                };
+                write!(f, " {letter}")?;
+            }
+            writeln!(f)?;
+        }
+        write!(f, "  a b c d e f g h")
     }
 }
@@ -1,5 +1,10 @@
 pub mod algorithms;
+pub mod clock;
 pub mod definitions;
 pub mod engine;
+pub mod eval;
 pub mod game;
+pub mod pgn;
+pub mod search;
 pub mod utils;
+pub mod zobrist;
@@ -0,0 +1,191 @@
+//! Export and import of games in Portable Game Notation.
+
+use std::io::{self, Write};
+
+use crate::core::engine::{Color, Game, GameEndState};
+
+/** Tags every PGN game carries, in required order, with values for unknown data. */
+pub const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+    ("Result", "*"),
+];
+
+/** PGN result token of the game ended with given state, `*` for unfinished games */
+pub fn result_token(end_state: Option<GameEndState>) -> &'static str {
+    match end_state {
+        Some(
+            GameEndState::CheckMate(Color::White)
+            | GameEndState::VariantWin(Color::White)
+            | GameEndState::ThreeCheckWin(Color::White)
+            | GameEndState::TimeForfeit(Color::White)
+            | GameEndState::Resignation(Color::White),
+        ) => "1-0",
+        Some(
+            GameEndState::CheckMate(Color::Black)
+            | GameEndState::VariantWin(Color::Black)
+            | GameEndState::ThreeCheckWin(Color::Black)
+            | GameEndState::TimeForfeit(Color::Black)
+            | GameEndState::Resignation(Color::Black),
+        ) => "0-1",
+        Some(
+            GameEndState::DrawStalemate
+            | GameEndState::DrawThreefoldRepetition
+            | GameEndState::DrawFiftyMoveRule
+            | GameEndState::DrawInsufficientMaterial
+            | GameEndState::DrawAgreement,
+        ) => "1/2-1/2",
+        None => "*",
+    }
+}
+
+/** Writes game in PGN with given tags, move by move.
+ *
+ * Games not starting from the initial position get `SetUp` and `FEN` tags. */
+pub fn write<W: Write>(w: &mut W, game: &Game, tags: &[(String, String)]) -> io::Result<()> {
+    for (name, value) in tags {
+        let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+        writeln!(w, "[{name} \"{value}\"]")?;
+    }
+    let (mut game, moves) = game.replay_start();
+    let start_fen = game.to_fen();
+    if start_fen != Game::default().to_fen() {
+        writeln!(w, "[SetUp \"1\"]")?;
+        writeln!(w, "[FEN \"{start_fen}\"]")?;
+    }
+    writeln!(w)?;
+    let mut end_state = None;
+    for (ply, _move) in moves.into_iter().enumerate() {
+        match game.current_player() {
+            Color::White => write!(w, "{}. ", game.fullmove_number())?,
+            Color::Black if ply == 0 => write!(w, "{}... ", game.fullmove_number())?,
+            Color::Black => {}
+        }
+        let (san, state) = game.execute_san(_move);
+        write!(w, "{san} ")?;
+        end_state = state;
+    }
+    writeln!(w, "{}", result_token(end_state))
+}
+
+/** Game in PGN, see `write` */
+pub fn to_pgn(game: &Game, tags: &[(String, String)]) -> String {
+    let mut pgn = Vec::new();
+    write(&mut pgn, game, tags).expect("Writing into memory can't fail");
+    String::from_utf8(pgn).expect("PGN is valid UTF-8")
+}
+
+/** Game in PGN with the seven tag roster followed by the rest of given tags.
+ *
+ * Roster tags missing from `tags` get placeholder values, `Result` always matches
+ * the game. Moves are written in SAN, positions before them are found by undoing
+ * played moves back to the start and replaying them, so full history is required. */
+pub fn export(game: &Game, tags: &[(String, String)]) -> String {
+    let result = result_token(game.last_end_state());
+    let mut all_tags: Vec<(String, String)> = SEVEN_TAG_ROSTER
+        .iter()
+        .map(|&(name, placeholder)| {
+            let value = match name {
+                "Result" => result,
+                _ => tags
+                    .iter()
+                    .find(|(tag, _)| tag == name)
+                    .map_or(placeholder, |(_, value)| value.as_str()),
+            };
+            (name.to_string(), value.to_string())
+        })
+        .collect();
+    all_tags.extend(
+        tags.iter()
+            .filter(|(tag, _)| !SEVEN_TAG_ROSTER.iter().any(|(name, _)| tag == name))
+            .cloned(),
+    );
+    to_pgn(game, &all_tags)
+}
+
+/** Game replayed from PGN movetext, starting from the `FEN` tag position if present.
+ *
+ * Comments, NAGs and variations are skipped, reading stops at the result token. */
+pub fn import(pgn: &str) -> Result<Game, String> {
+    let mut game = None;
+    let mut movetext = String::new();
+    for line in pgn.lines() {
+        let line = line.trim();
+        if let Some(tag) = line.strip_prefix('[').and_then(|tag| tag.strip_suffix(']')) {
+            let (name, value) = tag
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| format!("Malformed PGN tag '{line}'"))?;
+            let value = value.trim().trim_matches('"');
+            if name == "FEN" {
+                game = Some(Game::from_fen(value).map_err(|err| err.to_string())?);
+            }
+        } else {
+            movetext.push_str(line);
+            movetext.push('\n');
+        }
+    }
+    let mut game = game.unwrap_or_default();
+    for (index, san) in movetext_tokens(&movetext)?.into_iter().enumerate() {
+        if matches!(san, "1-0" | "0-1" | "1/2-1/2" | "*") {
+            break;
+        }
+        let _move = game
+            .parse_san(san)
+            .map_err(|err| format!("Move #{} '{san}': {err}", index + 1))?;
+        if game.execute(_move).is_some() {
+            break;
+        }
+    }
+    Ok(game)
+}
+
+/** Moves and result token of movetext without move numbers, comments, NAGs and variations */
+fn movetext_tokens(movetext: &str) -> Result<Vec<&str>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = movetext;
+    let mut depth = 0usize;
+    while let Some(next) = rest.chars().next() {
+        let skip = match next {
+            '{' => rest
+                .find('}')
+                .map(|end| end + 1)
+                .ok_or("Unterminated PGN comment")?,
+            ';' => rest.find('\n').unwrap_or(rest.len()),
+            '(' => {
+                depth += 1;
+                1
+            }
+            ')' => {
+                depth = depth.checked_sub(1).ok_or("Unbalanced PGN variation")?;
+                1
+            }
+            c if c.is_whitespace() => c.len_utf8(),
+            _ => {
+                let end = rest
+                    .find(|c: char| c.is_whitespace() || "{;()".contains(c))
+                    .unwrap_or(rest.len());
+                let word = &rest[..end];
+                // Move number glued to the move, like `12...e5`
+                let token = match word.find('.') {
+                    Some(dot) if word[..dot].chars().all(|c| c.is_ascii_digit()) => {
+                        word[dot..].trim_start_matches('.')
+                    }
+                    _ => word,
+                };
+                if depth == 0 && !token.is_empty() && !token.starts_with('$') {
+                    tokens.push(token);
+                }
+                end
+            }
+        };
+        rest = &rest[skip..];
+    }
+    if depth != 0 {
+        return Err("Unbalanced PGN variation".to_string());
+    }
+    Ok(tokens)
+}
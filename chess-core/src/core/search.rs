@@ -0,0 +1,125 @@
+//! Move search and helpers for it, which don't change what moves are legal.
+
+use std::cmp::Reverse;
+
+use crate::core::engine::{Board, CheckType, Color, Game, GameEndState, Move, MoveType, PieceType};
+use crate::core::eval::evaluate;
+
+/** Score of a delivered mate, bigger than any evaluation */
+pub const MATE_SCORE: i32 = 1_000_000;
+
+/** Sorts moves for search to try likely good ones first: captures by most valuable
+ * victim, then least valuable attacker, followed by checks and quiet moves.
+ *
+ * Sort is stable, so moves of the same kind keep their order. */
+pub fn order_moves(board: &Board, moves: &mut [Move]) {
+    moves.sort_by_key(|_move| match _move.move_type() {
+        MoveType::Capture(victim)
+        | MoveType::PromotionCapture(victim, _)
+        | MoveType::EnPassantCapture(victim, _) => {
+            let victim = PieceType::from(board.inside()[victim.position()]);
+            (0, Reverse(victim.value()), _move.piece().type_().value())
+        }
+        _ if _move.check() != CheckType::None => (1, Reverse(0), 0),
+        _ => (2, Reverse(0), 0),
+    });
+}
+
+/** Entry point for search: all possible moves, including under-promotions,
+ * with check info filled and ordered by `order_moves`. */
+pub fn bot_moves(game: &Game) -> Vec<Move> {
+    let mut moves = game.legal_moves_expanded();
+    order_moves(game.board(), &mut moves);
+    moves
+}
+
+/** Best move for the player to move, searched `depth` plies deep with material evaluation.
+ *
+ * Faster mates score higher, so a mate in one is always preferred. Tree is walked
+ * by executing and undoing moves on a single `light_clone` of the game. */
+pub fn best_move(game: &Game, depth: u8) -> Option<Move> {
+    let depth = depth.max(1);
+    let mut game = game.light_clone();
+    let mut alpha = -MATE_SCORE * 2;
+    let mut best = None;
+    for _move in bot_moves(&game) {
+        let score = score_and_undo(
+            &mut game,
+            _move.clone(),
+            depth,
+            alpha,
+            MATE_SCORE * 2,
+            &evaluate,
+        );
+        if best.is_none() || score > alpha {
+            alpha = score;
+            best = Some(_move);
+        }
+    }
+    best
+}
+
+/** Alpha-beta negamax, returns score of the position for the player to move.
+ *
+ * Game is left as it was, moves searched are undone. */
+fn alpha_beta(
+    game: &mut Game,
+    depth: u8,
+    mut alpha: i32,
+    beta: i32,
+    evaluate_fn: &dyn Fn(&Board, Color) -> i32,
+) -> i32 {
+    if depth == 0 {
+        return evaluate_fn(game.board(), game.current_player());
+    }
+    let mut best = -MATE_SCORE * 2;
+    for _move in game.legal_moves_expanded() {
+        let score = score_and_undo(game, _move, depth, alpha, beta, evaluate_fn);
+        best = best.max(score);
+        alpha = alpha.max(score);
+        if alpha >= beta {
+            break;
+        }
+    }
+    best
+}
+
+/** Score of the move for the player making it, with `depth` plies searched after it */
+pub fn score_move(
+    game: &Game,
+    _move: Move,
+    depth: u8,
+    evaluate_fn: &dyn Fn(&Board, Color) -> i32,
+) -> i32 {
+    score_and_undo(
+        &mut game.light_clone(),
+        _move,
+        depth.saturating_add(1),
+        -MATE_SCORE * 2,
+        MATE_SCORE * 2,
+        evaluate_fn,
+    )
+}
+
+/** Score of the move for the player making it, searched `depth - 1` plies after it */
+fn score_and_undo(
+    game: &mut Game,
+    _move: Move,
+    depth: u8,
+    alpha: i32,
+    beta: i32,
+    evaluate_fn: &dyn Fn(&Board, Color) -> i32,
+) -> i32 {
+    let score = match game.execute(_move) {
+        Some(
+            GameEndState::CheckMate(_)
+            | GameEndState::VariantWin(_)
+            | GameEndState::ThreeCheckWin(_),
+        ) => MATE_SCORE + depth as i32,
+        Some(_) => 0,
+        None => -alpha_beta(game, depth - 1, -beta, -alpha, evaluate_fn),
+    };
+    game.undo_last_move()
+        .expect("Move executed by search can be undone");
+    score
+}
@@ -19,41 +19,39 @@ impl Iterator for BetweenIterator {
     }
 }
 
-pub fn between(from: u8, to: u8) -> BetweenIterator {
-    // TODO: Check if they on one side of spectre
-    #[cfg(debug_assertions)]
-    if !is_in_diagonal_line(from, to) && !is_in_straight_line(from, to) {
-        panic!("Points can't form line to search between them!")
-    }
-    let diff = to.wrapping_sub(from);
-    // TODO: precompute table?
-    // const TABLE: [u8; 256] = {
-    //     let mut table = [0; 256];
-    //     ... something ...
-    //     table
-    // };
-    let step = if is_in_diagonal_line(from, to) {
-        let (file, rank) = (diff & 0x80 == 0, diff & 0x08 == 0);
-        match (rank, file) {
-            (true, true) => 0x11,
-            (true, false) => 0xf1,
-            (false, true) => 0x0f,
-            (false, false) => 0xef,
+/** Steps along lines indexed by `to.wrapping_sub(from)`, 0 for squares not on one line.
+ *
+ * Differences of 0x88 squares are unique modulo 256, so the table is exact. */
+const LINE_STEPS: [u8; 256] = {
+    let mut table = [0; 256];
+    let mut file_diff: i16 = -7;
+    while file_diff <= 7 {
+        let mut rank_diff: i16 = -7;
+        while rank_diff <= 7 {
+            if (file_diff != 0 || rank_diff != 0)
+                && (file_diff == 0 || rank_diff == 0 || file_diff.abs() == rank_diff.abs())
+            {
+                let diff = (file_diff * 16 + rank_diff) as u8;
+                table[diff as usize] = (file_diff.signum() * 16 + rank_diff.signum()) as u8;
+            }
+            rank_diff += 1;
         }
-    } else {
-        let (x_or_y, positive) = (
-            diff & 0x0f != 0,
-            (diff.wrapping_shr(4) | diff & 0x0f) & 0x08 == 0,
-        );
-        match (x_or_y, positive) {
-            (true, true) => 0x01,
-            (true, false) => 0xff,
-            (false, true) => 0x10,
-            (false, false) => 0xf0,
-        }
-    };
+        file_diff += 1;
+    }
+    table
+};
+
+/** Step leading from one square to the other, 0 if they aren't on one line */
+#[inline]
+pub fn line_step(from: u8, to: u8) -> u8 {
+    LINE_STEPS[to.wrapping_sub(from) as usize]
+}
+
+/** Squares strictly between two squares, empty if they aren't on one line */
+pub fn between(from: u8, to: u8) -> BetweenIterator {
+    let step = line_step(from, to);
     BetweenIterator {
-        current: from,
+        current: if step == 0 { to } else { from },
         target: to,
         step,
     }
@@ -91,6 +89,13 @@ pub fn distance(a: u8, b: u8) -> u8 {
     (file_diff >> 4) + rank_diff
 }
 
+/** Manhattan distance to the closest of four central squares */
+pub fn center_distance(pos: u8) -> u8 {
+    let (file, rank): (u8, u8) = unpack_pos(pos);
+    let to_center = |coord: u8| if coord < 4 { 3 - coord } else { coord - 4 };
+    to_center(file) + to_center(rank)
+}
+
 pub fn is_in_straight_line(a: u8, b: u8) -> bool {
     let file_diff = (a & 0xf0).abs_diff(b & 0xf0);
     let rank_diff = (a & 0x0f).abs_diff(b & 0x0f);
@@ -133,3 +138,11 @@ const POS_TO_STRING: [&str; 128] = [
 pub fn pos_to_str(pos: u8) -> &'static str {
     POS_TO_STRING[pos as usize]
 }
+
+/** Position of square in algebraic notation like "e4", inverse of `pos_to_str` */
+pub fn str_to_pos(square: &str) -> Option<u8> {
+    match square.as_bytes() {
+        &[file @ b'a'..=b'h', rank @ b'1'..=b'8'] => Some(compact_pos(rank - b'1', file - b'a')),
+        _ => None,
+    }
+}
@@ -0,0 +1,69 @@
+//! Zobrist keys used for position hashing.
+//!
+//! Tables are generated at compile time from a fixed seed, so hashes are
+//! reproducible across runs and builds.
+
+const SEED: u64 = 0x5EED_C4E5_5B0A_4D01;
+
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (state, z ^ (z >> 31))
+}
+
+/** Keys indexed by square (`file * 8 + rank`) and piece nibble (color bit + type). */
+pub const PIECE_KEYS: [[u64; 16]; 64] = {
+    let mut table = [[0; 16]; 64];
+    let mut state = SEED;
+    let mut square = 0;
+    while square < 64 {
+        let mut piece = 0;
+        while piece < 16 {
+            let (next_state, key) = splitmix64(state);
+            state = next_state;
+            table[square][piece] = key;
+            piece += 1;
+        }
+        square += 1;
+    }
+    table
+};
+
+/** Key toggled when Black is to move. */
+pub const BLACK_TO_MOVE: u64 = splitmix64(SEED ^ 0xB1AC_0000_0000_0000).1;
+
+/** Keys for castling rights: White king side, White queen side, Black king side, Black queen side. */
+pub const CASTLING_KEYS: [u64; 4] = {
+    let mut keys = [0; 4];
+    let mut state = SEED ^ 0xCA57_1E00_0000_0000;
+    let mut i = 0;
+    while i < 4 {
+        let (next_state, key) = splitmix64(state);
+        state = next_state;
+        keys[i] = key;
+        i += 1;
+    }
+    keys
+};
+
+/** Keys for en passant target square, indexed by its file. */
+pub const EN_PASSANT_KEYS: [u64; 8] = {
+    let mut keys = [0; 8];
+    let mut state = SEED ^ 0xE9A5_5A00_0000_0000;
+    let mut i = 0;
+    while i < 8 {
+        let (next_state, key) = splitmix64(state);
+        state = next_state;
+        keys[i] = key;
+        i += 1;
+    }
+    keys
+};
+
+/** Index of piece code inside of `PIECE_KEYS` row, only color and type matter. */
+#[inline]
+pub fn piece_index(code: u8) -> usize {
+    ((code & 0x80) >> 4 | code & 0x07) as usize
+}
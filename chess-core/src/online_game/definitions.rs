@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::{
     core::engine::{Board, Game, GameEndState, Move},
@@ -31,6 +32,23 @@ pub struct OnlineGame {
     pub sender: UnboundedSender<(Color, ClientMessage)>,
     pub white: Option<Client>,
     pub black: Option<Client>,
+    /** Clients joined full room, they only watch the game */
+    pub spectators: Vec<Client>,
+    /** Games without time control aren't timed */
+    pub time_control: Option<TimeControl>,
+    /** Reconnection tokens of Black and White seats, by color players joined with */
+    pub tokens: [String; 2],
+    /** Players have swapped colors they joined with after rematch */
+    pub colors_swapped: bool,
+    /** Both seats were taken, since then only seat tokens let players back in */
+    pub started: bool,
+}
+
+/** Time each player starts with and gets back after every move */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TimeControl {
+    pub base: Duration,
+    pub increment: Duration,
 }
 
 pub type Rooms = Arc<RwLock<HashMap<GameId, OnlineGame>>>;
@@ -44,6 +62,50 @@ pub enum ServerMessage {
     /// Board, LastMove, CurrentPlayer, YourColor
     GameStateSync(Board, Option<Move>, Color, Color),
     RoomId(String),
+    /// Draw can be claimed with `ClientMessage::ClaimDraw`
+    DrawClaimable,
+    /// Opponent offers a draw, answer with `ClientMessage::RespondDraw`
+    DrawOffered,
+    /// Whether draw offer was accepted
+    DrawResult(bool),
+    /// Board, LastMove, CurrentPlayer for spectators
+    SpectatorSync(Board, Option<Move>, Color),
+    /// Milliseconds left for White and Black, sent along with game state in timed games
+    ClockUpdate(u64, u64),
+    /// Token to reclaim the seat after a drop, passed as `?token=` on reconnection
+    SeatToken(String),
+}
+
+/** Whose move it is from the point of view of a client */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientTurn {
+    Mine,
+    Opponent,
+}
+
+/** Applies `GameStateSync` message on the client side.
+ *
+ * Previous game is resumed to keep its history, otherwise game is built from scratch. */
+pub fn apply_sync(
+    previous: Option<Game>,
+    board: Board,
+    last_move: Option<Move>,
+    current_player: Color,
+    my_color: Color,
+) -> (Game, ClientTurn) {
+    let game = match previous {
+        Some(mut game) => {
+            game.resume_from_sync(board, last_move, current_player);
+            game
+        }
+        None => Game::new(board, current_player, last_move),
+    };
+    let turn = if current_player == my_color {
+        ClientTurn::Mine
+    } else {
+        ClientTurn::Opponent
+    };
+    (game, turn)
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -52,9 +114,57 @@ pub enum ClientMessage {
     Disconnect,
     MakeMove(Move),
     Resigned,
+    ClaimDraw,
+    /// Offer stays until opponent answers or any move is made
+    OfferDraw,
+    /// Answer to opponent's draw offer
+    RespondDraw(bool),
+    /// New game with swapped colors starts once both players request it
+    RequestRematch,
 }
 
 impl OnlineGame {
+    /** Room with fresh seat tokens */
+    pub fn new(
+        id: GameId,
+        sender: UnboundedSender<(Color, ClientMessage)>,
+        time_control: Option<TimeControl>,
+    ) -> Self {
+        Self {
+            id,
+            game: Default::default(),
+            sender,
+            white: None,
+            black: None,
+            spectators: Vec::new(),
+            time_control,
+            tokens: [Uuid::new_v4().to_string(), Uuid::new_v4().to_string()],
+            colors_swapped: false,
+            started: false,
+        }
+    }
+
+    /** Color players joined with, whose token it is */
+    pub fn token_seat(&self, token: &str) -> Option<Color> {
+        match self
+            .tokens
+            .iter()
+            .position(|seat_token| seat_token == token)?
+        {
+            0 => Some(Color::Black),
+            _ => Some(Color::White),
+        }
+    }
+
+    /** Current color of the seat, which players joined with given color */
+    pub fn seat_color(&self, seat: Color) -> Color {
+        if self.colors_swapped {
+            seat.opposite()
+        } else {
+            seat
+        }
+    }
+
     pub fn get_player(&self, player: Color) -> Option<&Client> {
         match player {
             Color::Black => self.black.as_ref(),
@@ -1,26 +1,54 @@
+use std::time::Duration;
+
+use serde::Deserialize;
 use warp::{reject::Rejection, reply::Reply};
 
 use crate::online_game::definitions::*;
 use crate::online_game::logic::client_connection;
 
-pub async fn new_room_handler(ws: warp::ws::Ws, rooms: Rooms) -> Result<impl Reply, Rejection> {
+/** Query of new room request, like `?base=300&increment=5` in seconds */
+#[derive(Debug, Default, Deserialize)]
+pub struct TimeControlQuery {
+    pub base: Option<u64>,
+    pub increment: Option<u64>,
+}
+
+impl TimeControlQuery {
+    /** Game is timed only when base time is given */
+    pub fn time_control(&self) -> Option<TimeControl> {
+        Some(TimeControl {
+            base: Duration::from_secs(self.base?),
+            increment: Duration::from_secs(self.increment.unwrap_or(0)),
+        })
+    }
+}
+
+/** Query of existing room request, like `?token=...` to reclaim the seat after a drop */
+#[derive(Debug, Default, Deserialize)]
+pub struct ReconnectQuery {
+    pub token: Option<String>,
+}
+
+pub async fn new_room_handler(
+    query: TimeControlQuery,
+    ws: warp::ws::Ws,
+    rooms: Rooms,
+) -> Result<impl Reply, Rejection> {
     info!("Get new connection to websocket!");
-    Ok(ws.on_upgrade(|ws| client_connection(ws, rooms, None)))
+    let time_control = query.time_control();
+    Ok(ws.on_upgrade(move |ws| client_connection(ws, rooms, None, time_control, None)))
 }
 
 pub async fn existing_room_handler(
     room: String,
+    query: ReconnectQuery,
     ws: warp::ws::Ws,
     rooms: Rooms,
 ) -> Result<impl Reply, Rejection> {
     let result = if let Some(game) = rooms.clone().read().await.get(&room) {
-        // Trying to connect in already created game
-        if game.black.is_some() && game.white.is_some() {
-            // Room is full
-            return Err(warp::reject());
-        }
+        // Trying to connect in already created game, full room is joined as spectator
         let name = game.id.clone();
-        Ok(ws.on_upgrade(|ws| client_connection(ws, rooms, Some(name))))
+        Ok(ws.on_upgrade(|ws| client_connection(ws, rooms, Some(name), None, query.token)))
     } else {
         Err(warp::reject())
     };
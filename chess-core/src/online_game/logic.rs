@@ -1,12 +1,29 @@
-use crate::{core::engine::Game, Color};
+use crate::{
+    core::{
+        clock::Clock,
+        engine::{Game, GameEndState},
+    },
+    Color,
+};
 use futures::{FutureExt, StreamExt};
 use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio::time::Instant;
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use warp::filters::ws::{Message, WebSocket};
 
 use crate::online_game::definitions::*;
 
-pub async fn client_connection(ws: WebSocket, rooms: Rooms, room_name: Option<String>) {
+/** Seats new client in the room, time control is used only if room is created
+ *
+ * Client with a seat token takes back the seat it was given, if the seat is free.
+ * Once the game has started, clients without a token only watch it. */
+pub async fn client_connection(
+    ws: WebSocket,
+    rooms: Rooms,
+    room_name: Option<String>,
+    time_control: Option<TimeControl>,
+    token: Option<String>,
+) {
     let (client_ws_sender, client_ws_receiver) = ws.split();
     let (client_sender, client_rcv) = mpsc::unbounded_channel();
 
@@ -23,6 +40,7 @@ pub async fn client_connection(ws: WebSocket, rooms: Rooms, room_name: Option<St
         Uuid::new_v4().to_string()
     };
     let id = Uuid::new_v4();
+    // Connections without color are spectators
     let (sender, player) = {
         // Adding new client to game
         let mut rooms_l = rooms.write().await;
@@ -33,33 +51,70 @@ pub async fn client_connection(ws: WebSocket, rooms: Rooms, room_name: Option<St
             color: Color::White,
         };
         if let Some(game) = rooms_l.get_mut(&game_id) {
-            let color = if game.white.is_none() {
-                game.white = Some(client);
-                Color::White
+            let seat = if let Some(token) = token {
+                let Some(seat) = game.token_seat(&token) else {
+                    warn!("Room #{} client {} used unknown seat token", game_id, id);
+                    return;
+                };
+                if game.get_player(game.seat_color(seat)).is_some() {
+                    warn!(
+                        "Room #{} client {} used token of occupied {} seat",
+                        game_id, id, seat
+                    );
+                    return;
+                }
+                Some(seat)
+            } else if game.started {
+                None
+            } else if game.get_player(game.seat_color(Color::White)).is_none() {
+                Some(Color::White)
+            } else if game.get_player(game.seat_color(Color::Black)).is_none() {
+                Some(Color::Black)
             } else {
-                assert!(game.black.is_none(), "Game is full, but have new client!");
-                client.color = Color::Black;
-                game.black = Some(client);
-                Color::Black
+                None
             };
-            trace!("Added new client {} to '{}' room", id, game_id);
-            (game.sender.clone(), color)
+            if let Some(seat) = seat {
+                client.color = game.seat_color(seat);
+                send_message(
+                    &client,
+                    ServerMessage::SeatToken(game.tokens[seat as usize >> 7].clone()),
+                );
+                let color = client.color;
+                *game.get_player_mut(color) = Some(client);
+                game.started |= game.white.is_some() && game.black.is_some();
+            } else {
+                send_message(&client, ServerMessage::RoomId(game_id.clone()));
+                send_message(&client, spectator_sync(&game.game));
+                game.spectators.push(client);
+            }
+            trace!(
+                "Added new client {} to '{}' room as {:?}",
+                id,
+                game_id,
+                seat
+            );
+            (game.sender.clone(), seat)
         } else {
             let (sender, receiver) = mpsc::unbounded_channel();
-            let game = OnlineGame {
-                id: game_id.clone(),
-                game: Default::default(),
-                sender: sender.clone(),
-                white: Some(client),
-                black: None,
-            };
+            let mut game = OnlineGame::new(game_id.clone(), sender.clone(), time_control);
+            send_message(&client, ServerMessage::SeatToken(game.tokens[1].clone()));
+            game.white = Some(client);
             rooms_l.insert(game_id.clone(), game);
             trace!("Created new room '{}' for new client {}", game_id, id);
-            tokio::task::spawn(game_handler(receiver, rooms.clone(), game_id.clone()));
-            (sender, Color::White)
+            tokio::task::spawn(game_handler(
+                receiver,
+                rooms.clone(),
+                game_id.clone(),
+                time_control,
+            ));
+            (sender, Some(Color::White))
         }
     };
 
+    if let Some(player) = player {
+        let _ = sender.send((player, ClientMessage::Connected));
+    }
+
     client_ws_receiver
         .for_each(|msg| async {
             if msg.as_ref().map_or(false, |msg| msg.is_close()) {
@@ -73,9 +128,19 @@ pub async fn client_connection(ws: WebSocket, rooms: Rooms, room_name: Option<St
                 return;
             }
             let client_msg = msg.unwrap().try_into();
-            match client_msg {
-                Ok(msg) => sender.send((player, msg)).expect("Something got wrong"),
-                Err(err) => error!(
+            match (player, client_msg) {
+                (Some(player), Ok(msg)) => sender.send((player, msg)).expect("Something got wrong"),
+                (None, Ok(msg @ ClientMessage::MakeMove(_))) => warn!(
+                    "Room #{} spectator {} tried to make move {:?}",
+                    game_id, id, msg
+                ),
+                (None, Ok(msg)) => trace!(
+                    "Room #{} spectator {} sent ignored message {:?}",
+                    game_id,
+                    id,
+                    msg
+                ),
+                (_, Err(err)) => error!(
                     "Room #{} client {} failed to parse message! Err: {:?}",
                     game_id, id, err
                 ),
@@ -83,6 +148,13 @@ pub async fn client_connection(ws: WebSocket, rooms: Rooms, room_name: Option<St
         })
         .await;
 
+    let Some(player) = player else {
+        if let Some(room) = rooms.write().await.get_mut(&game_id) {
+            room.spectators.retain(|spectator| spectator.id != id);
+        }
+        trace!("Spectator {} was disconnected...", id);
+        return;
+    };
     // Often is a duplicate. but if something got really wrong with client
     //  better be safe than sorry
     let _ = sender.send((player, ClientMessage::Disconnect));
@@ -98,18 +170,95 @@ enum ServerState {
     GameFinished,
 }
 
-async fn game_handler(
+pub(crate) async fn game_handler(
     mut receiver: UnboundedReceiver<(Color, ClientMessage)>,
     rooms: Rooms,
     game_id: GameId,
+    time_control: Option<TimeControl>,
 ) {
     debug!("Game #{} Started game handler", game_id);
     let mut current_state = ServerState::NotStarted;
-    let mut game = Game::default();
+    let new_game = || match time_control {
+        Some(time_control) => {
+            Game::default().with_clock(Clock::new(time_control.base, time_control.increment))
+        }
+        None => Game::default(),
+    };
+    let mut game = new_game();
+    // Since when player to move is thinking, clock runs only in active game
+    let mut turn_started = Instant::now();
+    // Player whose draw offer waits for an answer
+    let mut draw_offer: Option<Color> = None;
+    // Rematch requests of Black and White after finished game
+    let mut rematch_requests = [false; 2];
+    // Connections keep color they joined with, colors are swapped on every rematch
+    let mut colors_swapped = false;
+    // Black and White players which are seated and announced
+    let mut connected = [false; 2];
     // TODO: Game logic!
-    while let Some((player, message)) = receiver.recv().await {
+    loop {
+        let flag_fall = match (&current_state, game.clock()) {
+            (ServerState::ActiveGame, Some(clock)) => Some(
+                clock
+                    .remaining(game.current_player())
+                    .saturating_sub(turn_started.elapsed()),
+            ),
+            _ => None,
+        };
+        let received = match flag_fall {
+            Some(time_left) => tokio::select! {
+                received = receiver.recv() => received,
+                _ = tokio::time::sleep(time_left) => {
+                    let end_state = game.tick(turn_started.elapsed());
+                    turn_started = Instant::now();
+                    if let Some(end_state) = end_state {
+                        trace!("Game #{} {} player ran out of time!", game_id, game.current_player());
+                        current_state = ServerState::GameFinished;
+                        finish_on_time(&rooms, &game_id, &game, end_state).await;
+                    }
+                    continue;
+                }
+            },
+            None => receiver.recv().await,
+        };
+        let Some((player, message)) = received else {
+            break;
+        };
+        let player = if colors_swapped {
+            player.opposite()
+        } else {
+            player
+        };
         match message {
+            ClientMessage::Connected if connected[player as usize >> 7] => {
+                // Reconnect logic may repeat the message, player only gets resynced
+                trace!(
+                    "Game #{} Received duplicate connection of {} player",
+                    game_id,
+                    player
+                );
+                send_message_by_id(
+                    &rooms,
+                    &game_id,
+                    player,
+                    ServerMessage::RoomId(game_id.clone()),
+                )
+                .await;
+                send_message_by_id(
+                    &rooms,
+                    &game_id,
+                    player,
+                    ServerMessage::GameStateSync(
+                        game.board().clone(),
+                        game.history().last_move(),
+                        game.current_player(),
+                        player,
+                    ),
+                )
+                .await;
+            }
             ClientMessage::Connected => {
+                connected[player as usize >> 7] = true;
                 match &current_state {
                     ServerState::NotStarted => {
                         current_state = ServerState::UnconnectedPlayer;
@@ -117,6 +266,7 @@ async fn game_handler(
                     }
                     ServerState::UnconnectedPlayer => {
                         current_state = ServerState::ActiveGame;
+                        turn_started = Instant::now();
                         trace!(
                             "Game #{} accepted missing {} player connection",
                             game_id,
@@ -144,6 +294,9 @@ async fn game_handler(
                             player,
                         ),
                     );
+                    if let Some(clock_update) = clock_update(&game) {
+                        send_message(client, clock_update);
+                    }
                     if matches!(current_state, ServerState::ActiveGame) {
                         send_message(client, ServerMessage::OpponentConnected);
                         send_message(
@@ -154,60 +307,75 @@ async fn game_handler(
                     }
                 }
             }
-            ClientMessage::Disconnect => match &current_state {
-                ServerState::UnconnectedPlayer => {
-                    if rooms
-                        .read()
-                        .await
-                        .get(&game_id)
-                        .unwrap()
-                        .get_player(player)
-                        .is_none()
-                    {
+            ClientMessage::Disconnect => {
+                connected[player as usize >> 7] = false;
+                match &current_state {
+                    ServerState::UnconnectedPlayer => {
+                        if rooms
+                            .read()
+                            .await
+                            .get(&game_id)
+                            .unwrap()
+                            .get_player(player)
+                            .is_none()
+                        {
+                            trace!(
+                                "Game #{} Received {} player duplicate disconnect message!",
+                                game_id,
+                                player
+                            );
+                            continue;
+                        }
+                        current_state = ServerState::GameCanceled;
                         trace!(
-                            "Game #{} Received {} player duplicate disconnect message!",
+                            "Game #{} last {} player is disconnected! Room is closed!",
                             game_id,
                             player
                         );
-                        continue;
+                        receiver.close();
                     }
-                    current_state = ServerState::GameCanceled;
-                    trace!(
-                        "Game #{} last {} player is disconnected! Room is closed!",
-                        game_id,
-                        player
-                    );
-                    receiver.close();
-                }
-                ServerState::ActiveGame => {
-                    current_state = ServerState::UnconnectedPlayer;
-                    trace!("Game #{} {} player is disconnected!", game_id, player);
-                    let mut rooms = rooms.write().await;
-                    let room = rooms.get_mut(&game_id).unwrap();
-                    *room.get_player_mut(player) = None;
-                    send_message(
-                        room.get_player(player.opposite()).unwrap(),
-                        ServerMessage::OpponentDisconnected,
-                    );
-                }
-                ServerState::GameCanceled | ServerState::GameFinished => {
-                    trace!("Game #{} {} player is disconnected!", game_id, player);
-                    if let Some(room) = rooms.read().await.get(&game_id) {
-                        if let Some(client) = room.get_player(player.opposite()) {
-                            send_message(client, ServerMessage::OpponentDisconnected);
-                        } else {
-                            trace!("Game #{} All player have left! Room is closed!", game_id);
-                            receiver.close();
+                    ServerState::ActiveGame => {
+                        // Clock stops until player is back, time thought so far counts
+                        let flagged = game.tick(turn_started.elapsed());
+                        turn_started = Instant::now();
+                        current_state = ServerState::UnconnectedPlayer;
+                        trace!("Game #{} {} player is disconnected!", game_id, player);
+                        if let Some(room) = rooms.write().await.get_mut(&game_id) {
+                            *room.get_player_mut(player) = None;
+                            send_message(
+                                room.get_player(player.opposite()).unwrap(),
+                                ServerMessage::OpponentDisconnected,
+                            );
+                        }
+                        if let Some(end_state) = flagged {
+                            trace!(
+                                "Game #{} {} player ran out of time!",
+                                game_id,
+                                game.current_player()
+                            );
+                            current_state = ServerState::GameFinished;
+                            finish_on_time(&rooms, &game_id, &game, end_state).await;
                         }
                     }
+                    ServerState::GameCanceled | ServerState::GameFinished => {
+                        trace!("Game #{} {} player is disconnected!", game_id, player);
+                        if let Some(room) = rooms.read().await.get(&game_id) {
+                            if let Some(client) = room.get_player(player.opposite()) {
+                                send_message(client, ServerMessage::OpponentDisconnected);
+                            } else {
+                                trace!("Game #{} All player have left! Room is closed!", game_id);
+                                receiver.close();
+                            }
+                        }
+                    }
+                    state => {
+                        error!(
+                            "Game #{} Received invalid message {:?} in state {:?} from {} player",
+                            game_id, message, state, player
+                        );
+                    }
                 }
-                state => {
-                    error!(
-                        "Game #{} Received invalid message {:?} in state {:?} from {} player",
-                        game_id, message, state, player
-                    );
-                }
-            },
+            }
             ClientMessage::MakeMove(client_move) => {
                 if !matches!(current_state, ServerState::ActiveGame) {
                     error!(
@@ -223,13 +391,18 @@ async fn game_handler(
                     );
                     continue;
                 }
-                let moves = game.get_possible_moves(true);
-                let same_move = moves.into_iter().find(|_move| {
-                    _move.piece() == client_move.piece()
-                        && _move.move_type() == client_move.move_type()
-                });
-                let end_state = if let Some(_move) = same_move {
-                    game.execute(_move)
+                let flagged = game.tick(turn_started.elapsed());
+                turn_started = Instant::now();
+                if let Some(end_state) = flagged {
+                    trace!("Game #{} {} player ran out of time!", game_id, player);
+                    current_state = ServerState::GameFinished;
+                    finish_on_time(&rooms, &game_id, &game, end_state).await;
+                    continue;
+                }
+                let end_state = if let Some(_move) = game.find_possible_move(&client_move) {
+                    draw_offer = None;
+                    let end_state = game.execute(_move);
+                    online_end_state(&game, end_state)
                 } else {
                     send_message_by_id(
                         &rooms,
@@ -245,7 +418,11 @@ async fn game_handler(
                     .await;
                     continue;
                 };
-                if let Some(room) = rooms.read().await.get(&game_id) {
+                if let Some(room) = rooms.write().await.get_mut(&game_id) {
+                    room.game = game.clone();
+                    for spectator in &room.spectators {
+                        send_message(spectator, spectator_sync(&game));
+                    }
                     send_message(
                         room.get_player(player).unwrap(),
                         ServerMessage::GameStateSync(
@@ -264,13 +441,166 @@ async fn game_handler(
                             player.opposite(),
                         ),
                     );
+                    if let Some(clock_update) = clock_update(&game) {
+                        broadcast_msg(room, clock_update);
+                    }
                     if let Some(end_state) = end_state {
                         current_state = ServerState::GameFinished;
                         broadcast_msg(room, ServerMessage::GameFinished(end_state));
+                    } else if game.can_claim_draw() {
+                        broadcast_msg(room, ServerMessage::DrawClaimable);
                     }
                 }
             }
-            ClientMessage::Resigned => todo!(),
+            ClientMessage::Resigned => {
+                if !matches!(current_state, ServerState::ActiveGame) {
+                    error!(
+                        "Game #{} Received Resigned message in state {:?} from {} player",
+                        game_id, current_state, player
+                    );
+                    continue;
+                }
+                current_state = ServerState::GameFinished;
+                game.finish(GameEndState::Resignation(player.opposite()));
+                trace!("Game #{} {} player resigned!", game_id, player);
+                broadcast_msg_by_id(
+                    &rooms,
+                    &game_id,
+                    ServerMessage::GameFinished(GameEndState::Resignation(player.opposite())),
+                )
+                .await;
+            }
+            ClientMessage::ClaimDraw => {
+                if !matches!(current_state, ServerState::ActiveGame) {
+                    error!(
+                        "Game #{} Received ClaimDraw message in state {:?} from {} player",
+                        game_id, current_state, player
+                    );
+                    continue;
+                }
+                let Some(end_state) = game.claimable_draw() else {
+                    info!(
+                        "Game #{} {} player claimed draw, which isn't available!",
+                        game_id, player
+                    );
+                    continue;
+                };
+                current_state = ServerState::GameFinished;
+                broadcast_msg_by_id(&rooms, &game_id, ServerMessage::GameFinished(end_state)).await;
+            }
+            ClientMessage::RequestRematch => {
+                if !matches!(current_state, ServerState::GameFinished) {
+                    error!(
+                        "Game #{} Received RequestRematch message in state {:?} from {} player",
+                        game_id, current_state, player
+                    );
+                    continue;
+                }
+                rematch_requests[player as usize >> 7] = true;
+                if rematch_requests.contains(&false) {
+                    trace!("Game #{} {} player requests rematch", game_id, player);
+                    continue;
+                }
+                rematch_requests = [false; 2];
+                draw_offer = None;
+                colors_swapped = !colors_swapped;
+                game = new_game();
+                turn_started = Instant::now();
+                current_state = ServerState::ActiveGame;
+                trace!("Game #{} Rematch started with swapped colors", game_id);
+                let mut rooms = rooms.write().await;
+                let Some(room) = rooms.get_mut(&game_id) else {
+                    continue;
+                };
+                std::mem::swap(&mut room.white, &mut room.black);
+                room.colors_swapped = colors_swapped;
+                room.game = game.clone();
+                for spectator in &room.spectators {
+                    send_message(spectator, spectator_sync(&game));
+                }
+                for color in [Color::White, Color::Black] {
+                    if let Some(client) = room.get_player_mut(color) {
+                        client.color = color;
+                        send_message(
+                            client,
+                            ServerMessage::GameStateSync(
+                                game.board().clone(),
+                                game.history().last_move(),
+                                game.current_player(),
+                                color,
+                            ),
+                        );
+                    }
+                }
+                if let Some(clock_update) = clock_update(&game) {
+                    broadcast_msg(room, clock_update);
+                }
+            }
+            ClientMessage::OfferDraw => {
+                if !matches!(current_state, ServerState::ActiveGame) {
+                    error!(
+                        "Game #{} Received OfferDraw message in state {:?} from {} player",
+                        game_id, current_state, player
+                    );
+                    continue;
+                }
+                match draw_offer {
+                    Some(offered_by) if offered_by == player => {
+                        info!(
+                            "Game #{} {} player repeats draw offer, which is still pending!",
+                            game_id, player
+                        );
+                    }
+                    // Offers from both sides are an agreement already
+                    Some(_) => {
+                        draw_offer = None;
+                        current_state = ServerState::GameFinished;
+                        game.finish(GameEndState::DrawAgreement);
+                        broadcast_msg_by_id(&rooms, &game_id, ServerMessage::DrawResult(true))
+                            .await;
+                        broadcast_msg_by_id(
+                            &rooms,
+                            &game_id,
+                            ServerMessage::GameFinished(GameEndState::DrawAgreement),
+                        )
+                        .await;
+                    }
+                    None => {
+                        draw_offer = Some(player);
+                        trace!("Game #{} {} player offers draw", game_id, player);
+                        send_message_by_id(
+                            &rooms,
+                            &game_id,
+                            player.opposite(),
+                            ServerMessage::DrawOffered,
+                        )
+                        .await;
+                    }
+                }
+            }
+            ClientMessage::RespondDraw(accepted) => {
+                if !matches!(current_state, ServerState::ActiveGame)
+                    || draw_offer != Some(player.opposite())
+                {
+                    error!(
+                        "Game #{} Received RespondDraw message in state {:?} without draw offer from {} player",
+                        game_id, current_state, player
+                    );
+                    continue;
+                }
+                draw_offer = None;
+                broadcast_msg_by_id(&rooms, &game_id, ServerMessage::DrawResult(accepted)).await;
+                if accepted {
+                    current_state = ServerState::GameFinished;
+                    game.finish(GameEndState::DrawAgreement);
+                    broadcast_msg_by_id(
+                        &rooms,
+                        &game_id,
+                        ServerMessage::GameFinished(GameEndState::DrawAgreement),
+                    )
+                    .await;
+                }
+            }
         }
     }
     debug!("Game #{} Finished game handler!", game_id);
@@ -286,6 +616,19 @@ async fn game_handler(
     }
 }
 
+/** Online games don't end on claimable draws, players have to claim them */
+pub(crate) fn online_end_state(
+    game: &Game,
+    end_state: Option<GameEndState>,
+) -> Option<GameEndState> {
+    match end_state {
+        Some(GameEndState::DrawThreefoldRepetition | GameEndState::DrawFiftyMoveRule) | None => {
+            game.automatic_draw()
+        }
+        end_state => end_state,
+    }
+}
+
 async fn broadcast_msg_by_id<T: Into<Message>>(rooms: &Rooms, game_id: &GameId, msg: T) {
     if let Some(room) = rooms.read().await.get(game_id) {
         broadcast_msg(room, msg);
@@ -305,14 +648,38 @@ async fn send_message_by_id<T: Into<Message>>(
     }
 }
 
+/** Ends the game lost on time, players get final clock state */
+async fn finish_on_time(rooms: &Rooms, game_id: &GameId, game: &Game, end_state: GameEndState) {
+    if let Some(room) = rooms.read().await.get(game_id) {
+        if let Some(clock_update) = clock_update(game) {
+            broadcast_msg(room, clock_update);
+        }
+        broadcast_msg(room, ServerMessage::GameFinished(end_state));
+    }
+}
+
+fn clock_update(game: &Game) -> Option<ServerMessage> {
+    let clock = game.clock()?;
+    Some(ServerMessage::ClockUpdate(
+        clock.remaining(Color::White).as_millis() as u64,
+        clock.remaining(Color::Black).as_millis() as u64,
+    ))
+}
+
+/** Sends message to both players and all spectators */
 fn broadcast_msg<T: Into<Message>>(room: &OnlineGame, msg: T) {
     let message = msg.into();
-    if let Some(client) = &room.white {
+    for client in room.white.iter().chain(&room.black).chain(&room.spectators) {
         let _ = client.sender.send(Ok(message.clone()));
     }
-    if let Some(client) = &room.black {
-        let _ = client.sender.send(Ok(message));
-    }
+}
+
+fn spectator_sync(game: &Game) -> ServerMessage {
+    ServerMessage::SpectatorSync(
+        game.board().clone(),
+        game.history().last_move(),
+        game.current_player(),
+    )
 }
 
 fn send_message<T: Into<warp::ws::Message>>(client: &Client, msg: T) {
@@ -17,11 +17,13 @@ async fn main() {
     let hello = warp::path!("hello" / String).map(|name| format!("Hello, {}!", name));
 
     let new_room = warp::path("ws")
+        .and(warp::query())
         .and(warp::ws())
         .and(with(rooms.clone()))
         .and_then(new_room_handler);
 
     let existing_room = warp::path!("ws" / String)
+        .and(warp::query())
         .and(warp::ws())
         .and(with(rooms.clone()))
         .and_then(existing_room_handler);
@@ -1,10 +1,22 @@
-use crate::core::definitions::{Cell, Figure};
+use crate::core::definitions::{Cell, Figure, ImplicitMove};
 
-use self::core::engine::{Board, Game, GameEndState, Piece};
-use self::core::game::ui_board;
-use self::core::utils::{between, compact_pos, is_in_diagonal_line, is_in_straight_line};
-use self::utils::perf_test;
+use self::core::algorithms;
+use self::core::clock::Clock;
+use self::core::engine::{
+    Board, CastlingRights, CastlingSide, CheckType, Color, Disambiguation, FenError, Game,
+    GameEndState, GameMode, MatePattern, Move, MoveError, MoveType, Piece, PieceType,
+};
+use self::core::eval::evaluate;
+use self::core::game::{self as ui, display_cell, ui_board};
+use self::core::pgn;
+use self::core::search::{self, best_move, order_moves};
+use self::core::utils::{
+    between, center_distance, compact_pos, distance, is_in_diagonal_line, is_in_straight_line,
+    is_valid_coord, line_step, pos_to_str, str_to_pos,
+};
+use self::utils::{perf_test, perft, perft_divide};
 use rand::seq::IteratorRandom;
+use std::time::Duration;
 
 use super::*;
 
@@ -47,6 +59,51 @@ fn math() {
     assert!(cells == vec![86, 101]);
 }
 
+#[test]
+fn square_names() {
+    assert_eq!(str_to_pos("a1"), Some(0x00));
+    assert_eq!(str_to_pos("h8"), Some(0x77));
+    assert_eq!(str_to_pos("e4"), Some(0x34));
+    for square in ["i9", "e", "", "e44", "a0", "E4"] {
+        assert_eq!(str_to_pos(square), None, "'{square}' isn't a square");
+    }
+    for pos in (0..128u8).filter(|pos| is_valid_coord(*pos)) {
+        assert_eq!(str_to_pos(pos_to_str(pos)), Some(pos));
+    }
+    // FEN en passant field is read with it too
+    assert!(Game::from_fen("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1").is_ok());
+    assert!(Game::from_fen("4k3/8/8/8/4P3/8/8/4K3 b - e4 0 1").is_err());
+    assert!(Game::from_fen("4k3/8/8/8/4P3/8/8/4K3 b - x3 0 1").is_err());
+}
+
+#[test]
+fn between_on_all_lines() {
+    for from in (0..128u8).filter(|pos| is_valid_coord(*pos)) {
+        for to in (0..128u8).filter(|pos| is_valid_coord(*pos)) {
+            let cells: Vec<_> = between(from, to).collect();
+            let on_line =
+                from != to && (is_in_straight_line(from, to) || is_in_diagonal_line(from, to));
+            if !on_line {
+                assert!(cells.is_empty(), "{from:#x} and {to:#x} aren't on one line");
+                continue;
+            }
+            let steps = distance(from, to) as usize;
+            let expected = if is_in_diagonal_line(from, to) {
+                steps / 2
+            } else {
+                steps
+            } - 1;
+            assert_eq!(cells.len(), expected, "{from:#x} to {to:#x}");
+            let step = line_step(from, to);
+            assert_eq!(between(to, from).count(), expected);
+            assert!(cells
+                .iter()
+                .enumerate()
+                .all(|(i, cell)| *cell == from.wrapping_add(step.wrapping_mul(i as u8 + 1))));
+        }
+    }
+}
+
 #[test]
 fn straight_line() {
     const STRAIGHT_LINE: [u8; 9] = [0x02, 0x12, 0x20, 0x21, 0x22, 0x23, 0x24, 0x32, 0x42];
@@ -107,22 +164,79 @@ fn obstruction() {
     let board = Board::default();
     assert!(
         player_board(&board, Color::White)
-            == ui_board(&board.clone().hide_and_obstruct(Color::White)),
+            == ui_board(&board.clone().hide_and_obstruct(Color::White, None)),
         "White board are obstructed incorrectly!"
     );
     assert!(
         player_board(&board, Color::Black)
-            == ui_board(&board.clone().hide_and_obstruct(Color::Black)),
+            == ui_board(&board.clone().hide_and_obstruct(Color::Black, None)),
         "Black board are obstructed incorrectly!"
     );
 }
 
+#[test]
+fn hidden_pin_still_enforced() {
+    // Rook on e8 is out of white's sight, but knight on e2 is pinned anyway
+    let game = Game::from_fen("k3r3/8/8/8/8/8/4N3/4K3 w - - 0 1").unwrap();
+    let vision = game.board().clone().hide_and_obstruct(Color::White, None);
+    assert_eq!(vision.inside()[0x74], 0x40);
+    assert!(game
+        .legal_moves_expanded()
+        .iter()
+        .all(|_move| _move.piece().position() != 0x14));
+}
+
+#[test]
+fn fog_pinned_piece_has_no_moves() {
+    // Bishop on a5 pins the rook on d2 diagonally, White doesn't see it
+    let game = Game::from_fen("7k/8/8/b7/8/8/3R4/4K3 w - - 0 1").unwrap();
+    let vision = game.vision(Color::White);
+    assert_eq!(vision.inside()[0x40], 0x40);
+    assert_eq!(vision.inside()[0x13], game.board().inside()[0x13]);
+    let moves = game.legal_moves_expanded();
+    assert!(!moves.is_empty());
+    assert!(moves.iter().all(|_move| _move.piece().position() != 0x13));
+}
+
+#[test]
+fn fog_shows_en_passant_pawn() {
+    let mut game = Game::from_fen("4k3/3p4/8/4P3/8/8/8/4K3 b - - 0 1").unwrap();
+    play(&mut game, "d7d5");
+    let pawn = game.board().inside()[0x43];
+    // Pawn next to e5 pawn is out of its sight unless it can be captured en passant
+    let hidden = game.board().clone().hide_and_obstruct(Color::White, None);
+    assert_eq!(hidden.inside()[0x43], 0x40);
+    let vision = game.vision(Color::White);
+    assert_eq!(vision.inside()[0x43], pawn);
+    assert_eq!(vision.inside()[0x53], 0x00);
+    assert!(game
+        .legal_moves_expanded()
+        .iter()
+        .any(|_move| _move.to_uci() == "e5d6"));
+}
+
+#[test]
+fn fog_view_follows_executed_move() {
+    let mut game = Game::default();
+    play(&mut game, "e2e4");
+    assert_eq!(game.current_player(), Color::Black);
+    let pawn = game.board().inside()[0x34];
+    assert_eq!(Piece::from_code(pawn, 0x34).type_(), PieceType::Pawn);
+    // Mover keeps sight of the pawn, opponent's pawns see only up to the fifth rank
+    let white = game.board().clone().hide_and_obstruct(Color::White, None);
+    assert_eq!(white.inside()[0x34], pawn);
+    assert_eq!(white.inside()[0x14], 0x00);
+    let black = game.board().clone().hide_and_obstruct(Color::Black, None);
+    assert_eq!(black.inside()[0x34], 0x40);
+    assert_eq!(black.inside()[0x44], 0x00);
+}
+
 #[test]
 #[ignore = "slow"]
 fn random_moves_game() {
     fn make_random_move(game: &mut Game) -> Option<GameEndState> {
         game.execute(
-            game.get_possible_moves(true)
+            game.legal_moves_expanded()
                 .into_iter()
                 .choose(&mut rand::thread_rng())
                 .unwrap(),
@@ -144,31 +258,2652 @@ fn fen_parsing() {
     }
 }
 
-macro_rules! perf_tests {
-    ($fen_string:literal $($(#[$attr:meta])* $name:ident: $value:expr)*) => {
-    $(
-        #[test]
-        $(#[$attr])*
-        fn $name() {
-            let fen_string = $fen_string;
-            let (expected, depth) = $value;
-            assert!(perf_test(fen_string, depth, expected, true, true), "Results don't match up");
+fn play(game: &mut Game, _move: &str) -> Option<GameEndState> {
+    let _move = game
+        .legal_moves_expanded()
+        .into_iter()
+        .find(|possible| possible.to_string() == _move)
+        .unwrap_or_else(|| panic!("Move {_move} is not possible"));
+    game.execute(_move)
+}
+
+#[test]
+fn repetition_debug() {
+    let mut game = Game::default();
+    let moves = ["g1f3", "g8f6", "f3g1", "f6g8"];
+    for _move in moves.iter().chain(moves.iter()) {
+        assert!(play(&mut game, _move).is_none());
+    }
+    assert!(matches!(
+        play(&mut game, "g1f3"),
+        Some(GameEndState::DrawThreefoldRepetition)
+    ));
+    let occurrences = game.repetition_debug();
+    assert_eq!(
+        occurrences.len(),
+        4,
+        "Only four distinct positions were played"
+    );
+    let repeated: Vec<_> = occurrences
+        .iter()
+        .filter(|(_, count)| *count >= 3)
+        .collect();
+    assert_eq!(repeated.len(), 1);
+    assert_eq!(repeated[0].0, occurrences[0].0);
+    assert_eq!(repeated[0].1, 3);
+}
+
+#[test]
+fn mobility() {
+    let game = Game::from_fen("4k3/8/8/8/3Q4/8/8/4K3 w - - 0 1").unwrap();
+    let queen = game.board().get(3, 3);
+    assert_eq!(queen.type_(), PieceType::Queen);
+    assert_eq!(game.board().mobility_of(queen), 27);
+    let board = Board::default();
+    assert_eq!(board.mobility_of(board.get(0, 6)), 2, "Knight on g1");
+    assert_eq!(board.mobility_of(board.get(1, 4)), 2, "Pawn on e2");
+    assert_eq!(board.mobility_of(board.get(0, 0)), 0, "Rook on a1");
+}
+
+#[test]
+fn quiet_position() {
+    assert!(Game::default().is_quiet());
+    // Black queen on d5 is hanging
+    let game = Game::from_fen("4k3/8/8/3q4/8/2N5/8/4K3 w - - 0 1").unwrap();
+    assert!(!game.is_quiet());
+    // Queen is defended by pawn, capturing it with a queen is just a trade
+    let game = Game::from_fen("4k3/8/2p5/3q4/8/8/3Q4/4K3 w - - 0 1").unwrap();
+    assert!(game.is_quiet());
+    // Side in check is never quiet
+    let game = Game::from_fen("4k3/8/8/8/8/8/4r3/4K3 w - - 0 1").unwrap();
+    assert!(!game.is_quiet());
+}
+
+#[test]
+fn fen_clocks() {
+    const FEN: &str = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 5 12";
+    let mut game = Game::from_fen(FEN).unwrap();
+    assert_eq!(game.halfmove_clock(), 5);
+    assert_eq!(game.fullmove_number(), 12);
+    assert_eq!(game.to_fen(), FEN);
+    assert!(play(&mut game, "a1b1").is_none());
+    assert!(play(&mut game, "e8e7").is_none());
+    assert_eq!(game.halfmove_clock(), 7);
+    assert_eq!(game.fullmove_number(), 13);
+    let fen = game.to_fen();
+    assert_eq!(fen, "r6r/4k3/8/8/8/8/8/1R2K2R w K - 7 13");
+    assert_eq!(Game::from_fen(&fen).unwrap().to_fen(), fen);
+    game.undo_last_move().unwrap();
+    game.undo_last_move().unwrap();
+    assert_eq!(game.to_fen(), FEN);
+}
+
+#[test]
+fn to_fen_round_trip() {
+    for fen in [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    ] {
+        assert_eq!(Game::from_fen(fen).unwrap().to_fen(), fen);
+    }
+    assert_eq!(
+        Game::default().to_fen(),
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+    );
+}
+
+#[test]
+fn fen_tolerance() {
+    const FEN: &str = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+    for fen in [
+        "  rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1  \n",
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR  b  KQkq\te3 0 1",
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3",
+    ] {
+        assert_eq!(Game::from_fen(fen).unwrap().to_fen(), FEN);
+    }
+    // Missing castling and en passant fields mean there are none
+    let game = Game::from_fen("4k3/8/8/8/8/8/8/R3K3 w").unwrap();
+    assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/8/R3K3 w - - 0 1");
+    assert_eq!(
+        Game::from_fen("4k3/8/8/8/8/8/8/4K3 b - e").unwrap_err(),
+        FenError::Truncated("en passant square")
+    );
+    assert!(matches!(
+        Game::from_fen("4k3/8/8/8/8/8/8/4K3 b - e5"),
+        Err(FenError::Invalid(_))
+    ));
+    assert_eq!(
+        Game::from_fen("4k3/8/8/8/8/8/8/4K3").unwrap_err(),
+        FenError::Truncated("active player")
+    );
+    assert_eq!(
+        Game::from_fen("   ").unwrap_err(),
+        FenError::Truncated("piece placement")
+    );
+}
+
+#[test]
+fn fen_malformed_ranks() {
+    // Rank with fewer than 8 files described
+    assert!(Game::from_fen("4k2/8/8/8/8/8/8/4K3 w - - 0 1").is_err());
+    assert!(Game::from_fen("4k3/8/8/8/8/8/8/4K2 w - - 0 1").is_err());
+    assert!(Game::from_fen("4k3/8/8/8/8/8/8/4K3x w - - 0 1").is_err());
+    assert!(Game::from_fen("4k3/8/8/8/8/8/8/4K3 w").is_ok());
+}
+
+#[test]
+fn fen_castling_and_en_passant() {
+    for fen in [
+        "r3k2r/8/8/8/8/8/8/R3K2R w Kq - 0 1",
+        "r3k2r/8/8/8/8/8/8/R3K2R b Qk - 3 9",
+        "r3k3/8/8/8/8/8/8/4K2R w - - 0 1",
+    ] {
+        assert_eq!(Game::from_fen(fen).unwrap().to_fen(), fen);
+    }
+    let mut game =
+        Game::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+    play(&mut game, "e2e4");
+    let white_push = game.to_fen();
+    assert_eq!(
+        white_push,
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+    );
+    play(&mut game, "d7d5");
+    let black_push = game.to_fen();
+    assert_eq!(
+        black_push,
+        "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2"
+    );
+    for fen in [white_push, black_push] {
+        let restored = Game::from_fen(&fen).unwrap();
+        assert_eq!(restored.to_fen(), fen);
+    }
+    // Restored target square still allows en passant capture
+    let mut game = Game::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+    assert!(play(&mut game, "e5d6").is_none());
+    assert_eq!(game.to_fen(), "4k3/8/3P4/8/8/8/8/4K3 b - - 0 1");
+    let mut game = Game::from_fen("4k3/8/8/8/3Pp3/8/8/4K3 b - d3 0 1").unwrap();
+    assert!(play(&mut game, "e4d3").is_none());
+    assert_eq!(game.to_fen(), "4k3/8/8/8/8/3p4/8/4K3 w - - 0 2");
+}
+
+#[test]
+fn promotion_moves_from() {
+    let game = Game::from_fen("1n2k3/P7/8/8/8/8/7P/4K3 w - - 0 1").unwrap();
+    let mut moves: Vec<_> = game
+        .promotion_moves_from(0x60)
+        .iter()
+        .map(Move::to_uci)
+        .collect();
+    moves.sort();
+    assert_eq!(
+        moves,
+        ["a7a8b", "a7a8n", "a7a8q", "a7a8r", "a7b8b", "a7b8n", "a7b8q", "a7b8r"]
+    );
+    assert!(game.promotion_moves_from(0x17).is_empty());
+}
+
+#[test]
+fn mate_patterns() {
+    let smothered = Game::from_fen("6rk/5Npp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+    assert_eq!(smothered.mate_pattern(), Some(MatePattern::Smothered));
+    let back_rank = Game::from_fen("3R2k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+    assert_eq!(back_rank.mate_pattern(), Some(MatePattern::BackRank));
+    let white_back_rank = Game::from_fen("6k1/8/8/8/8/8/PPP5/1K5r w - - 0 1").unwrap();
+    assert_eq!(white_back_rank.mate_pattern(), Some(MatePattern::BackRank));
+    // King can still escape to c6
+    let check = Game::from_fen("8/8/8/3k4/3Q4/2P1K3/8/8 b - - 0 1").unwrap();
+    assert_eq!(check.mate_pattern(), None);
+    let other = Game::from_fen("k7/1Q6/2K5/8/8/8/8/8 b - - 0 1").unwrap();
+    assert_eq!(other.mate_pattern(), Some(MatePattern::Other));
+    assert_eq!(Game::default().mate_pattern(), None);
+}
+
+#[test]
+fn castling_moves() {
+    assert!(Game::default().castling_moves().is_empty());
+    // Bishop on c4 covers g8, so black can castle queen side only
+    let game = Game::from_fen("r3k2r/8/8/8/2B5/8/8/R3K2R b KQkq - 0 1").unwrap();
+    let moves: Vec<_> = game.castling_moves().iter().map(Move::to_uci).collect();
+    assert_eq!(moves, ["e8c8"]);
+    // Black rook on d-file leaves white only king side
+    let game = Game::from_fen("r2rk3/8/8/8/8/8/8/R3K2R w KQq - 0 1").unwrap();
+    let moves = game.castling_moves();
+    assert_eq!(moves.len(), 1);
+    assert!(matches!(
+        moves[0].move_type(),
+        MoveType::Castling(CastlingSide::KingSide, _)
+    ));
+}
+
+#[test]
+fn castling_through_attacked_squares() {
+    // Queens cover d- and f-files squares on the king path
+    for fen in [
+        "r3k2r/8/3Q4/8/8/5q2/8/R3K2R b KQkq - 0 1",
+        "r3k2r/8/5Q2/8/8/3q4/8/R3K2R w KQkq - 0 1",
+    ] {
+        let game = Game::from_fen(fen).unwrap();
+        assert!(!game
+            .legal_moves_expanded()
+            .iter()
+            .any(|_move| matches!(_move.move_type(), MoveType::Castling(_, _))));
+    }
+}
+
+#[test]
+fn unicode_board() {
+    let board = Board::default();
+    let white = ui::unicode_board(&board, Color::White);
+    let lines: Vec<_> = white.lines().collect();
+    assert_eq!(lines.len(), 8);
+    assert_eq!(lines[0], "♜♞♝♛♚♝♞♜");
+    assert_eq!(lines[4], " · · · ·");
+    assert_eq!(lines[5], "· · · · ");
+    assert_eq!(lines[7], "♖♘♗♕♔♗♘♖");
+    let black = ui::unicode_board(&board, Color::Black);
+    let lines: Vec<_> = black.lines().collect();
+    assert_eq!(lines[0], "♖♘♗♔♕♗♘♖");
+    assert_eq!(lines[3], "· · · · ");
+    assert_eq!(lines[7].chars().nth(3), Some('♚'));
+}
+
+#[test]
+fn board_display() {
+    let text = Board::default().to_string();
+    let lines: Vec<_> = text.lines().collect();
+    assert_eq!(lines.len(), 9);
+    assert_eq!(lines[0], "8 r n b q k b n r");
+    assert_eq!(lines[4], "4 . . . . . . . .");
+    assert_eq!(lines[7], "1 R N B Q K B N R");
+    assert_eq!(lines[8], "  a b c d e f g h");
+}
+
+#[test]
+fn fen_clocks_imported() {
+    // Kings are required, so empty board from the request can't be loaded
+    assert!(Game::from_fen("8/8/8/8/8/8/8/8 w - - 49 120").is_err());
+    let mut game = Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 49 120").unwrap();
+    assert_eq!(game.halfmove_clock(), 49);
+    assert_eq!(game.fullmove_number(), 120);
+    play(&mut game, "e1d1");
+    play(&mut game, "e8d8");
+    assert_eq!(game.halfmove_clock(), 51);
+    assert_eq!(game.fullmove_number(), 121);
+}
+
+#[test]
+fn fifty_move_rule() {
+    let mut game = Game::from_fen("7k/8/8/3p4/3P4/8/8/K7 w - - 90 60").unwrap();
+    let line = [
+        "a1b1", "h8g8", "b1c1", "g8f8", "c1d1", "f8e8", "d1e1", "e8d8", "e1f1",
+    ];
+    for _move in line {
+        assert!(play(&mut game, _move).is_none(), "Draw before {_move}");
+    }
+    assert_eq!(game.halfmove_clock(), 99);
+    assert!(matches!(
+        play(&mut game, "d8c8"),
+        Some(GameEndState::DrawFiftyMoveRule)
+    ));
+    assert_eq!(game.halfmove_clock(), 100);
+    game.undo_last_move().unwrap();
+    assert_eq!(game.halfmove_clock(), 99);
+    // Mate on the hundredth ply takes priority
+    let mut game = Game::from_fen("7k/5K2/8/6Q1/8/8/8/8 w - - 99 80").unwrap();
+    assert!(matches!(
+        play(&mut game, "g5g7"),
+        Some(GameEndState::CheckMate(Color::White))
+    ));
+}
+
+#[test]
+fn pgn_export() {
+    let mut game = Game::default();
+    for _move in ["e2e4", "e7e5", "f1c4", "b8c6", "d1h5", "g8f6", "h5f7"] {
+        play(&mut game, _move);
+    }
+    let tags = [
+        ("White".to_string(), "Scholar".to_string()),
+        ("Black".to_string(), "Victim".to_string()),
+    ];
+    let mut written = Vec::new();
+    pgn::write(&mut written, &game, &tags).unwrap();
+    let pgn = pgn::to_pgn(&game, &tags);
+    assert_eq!(String::from_utf8(written).unwrap(), pgn);
+    assert_eq!(
+        pgn,
+        "[White \"Scholar\"]\n[Black \"Victim\"]\n\n\
+         1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7# 1-0\n"
+    );
+    let mut game = Game::from_fen("4k3/8/8/8/8/8/r7/4K3 b - - 0 30").unwrap();
+    play(&mut game, "a2a1");
+    play(&mut game, "e1d2");
+    assert_eq!(
+        pgn::to_pgn(&game, &[]),
+        "[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/r7/4K3 b - - 0 30\"]\n\n\
+         30... Ra1+ 31. Kd2 *\n"
+    );
+    let tags = [("Event".to_string(), "\"Open\" C:\\Games".to_string())];
+    assert!(
+        pgn::to_pgn(&Game::default(), &tags).starts_with("[Event \"\\\"Open\\\" C:\\\\Games\"]\n")
+    );
+}
+
+#[test]
+fn pgn_seven_tag_roster() {
+    let mut game = Game::default();
+    for _move in ["e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "a7a6"] {
+        play(&mut game, _move);
+    }
+    assert_eq!(game.last_end_state(), None);
+    let tags = [
+        ("Annotator".to_string(), "Nobody".to_string()),
+        ("White".to_string(), "Ruy".to_string()),
+        ("Result".to_string(), "1-0".to_string()),
+    ];
+    assert_eq!(
+        pgn::export(&game, &tags),
+        "[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n\
+         [White \"Ruy\"]\n[Black \"?\"]\n[Result \"*\"]\n[Annotator \"Nobody\"]\n\n\
+         1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 *\n"
+    );
+
+    let mut game = Game::default();
+    for _move in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+        play(&mut game, _move);
+    }
+    assert_eq!(
+        game.last_end_state(),
+        Some(GameEndState::CheckMate(Color::Black))
+    );
+    let pgn = pgn::export(&game, &[]);
+    assert!(pgn.contains("[Result \"0-1\"]\n"));
+    assert!(pgn.ends_with("1. f3 e5 2. g4 Qh4# 0-1\n"));
+}
+
+#[test]
+fn pgn_import() {
+    let pgn = "[Event \"Casual\"]\n[White \"Ruy\"]\n\n\
+        1. e4 {King pawn} e5 2. Nf3 $1 Nc6 (2... d6 3. d4) ; Main line\n\
+        3.Bb5 a6 *\n";
+    let game = pgn::import(pgn).unwrap();
+    assert_eq!(
+        game.to_fen(),
+        "r1bqkbnr/1ppp1ppp/p1n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 0 4"
+    );
+    let restored = pgn::import(&pgn::export(&game, &[])).unwrap();
+    assert_eq!(restored.to_fen(), game.to_fen());
+
+    let pgn = "[FEN \"4k3/8/8/8/8/8/r7/4K3 b - - 0 30\"]\n\n30... Ra1+ 31. Kd2 Ra2+ 1/2-1/2";
+    assert_eq!(
+        pgn::import(pgn).unwrap().to_fen(),
+        "4k3/8/8/8/8/8/r2K4/8 w - - 3 32"
+    );
+
+    let err = pgn::import("1. e4 e5 2. Ke3 Nc6").unwrap_err();
+    assert!(err.starts_with("Move #3 'Ke3'"), "{err}");
+    assert!(pgn::import("1. e4 {never closed").is_err());
+    assert!(pgn::import("1. e4 (1. d4 e5").is_err());
+}
+
+#[test]
+fn insufficient_material_draws() {
+    for (fen, _move) in [
+        ("8/8/8/4k3/8/4K3/8/8 w - - 0 1", "e3d3"),
+        ("8/8/8/4k3/8/4K3/8/5B2 w - - 0 1", "f1d3"),
+        ("8/8/8/4k3/8/4K3/8/5N2 b - - 0 1", "e5d5"),
+        // Both bishops on light squares
+        ("2b5/8/8/4k3/8/4K3/8/5B2 w - - 0 1", "e3d2"),
+        // Knight promotion leaves bare kings with a knight
+        ("8/8/8/8/8/4k3/p7/4K3 b - - 0 1", "a2a1n"),
+    ] {
+        let mut game = Game::from_fen(fen).unwrap();
+        let _move = game
+            .legal_moves_expanded()
+            .into_iter()
+            .find(|possible| possible.to_uci() == _move)
+            .unwrap();
+        assert!(
+            matches!(
+                game.execute(_move),
+                Some(GameEndState::DrawInsufficientMaterial)
+            ),
+            "{fen}"
+        );
+    }
+    let mut game = Game::from_fen("1b6/8/8/4k3/8/4K3/8/5B2 w - - 0 1").unwrap();
+    assert!(play(&mut game, "e3d2").is_none());
+}
+
+#[test]
+fn move_constructors() {
+    let pawn = Piece::from_code(0x81 | 0x08, 0x60);
+    let knight = Piece::from_code(0x82, 0x01);
+    let black_rook = Piece::from_code(0x04, 0x71);
+    let own_rook = Piece::from_code(0x84, 0x71);
+    let promotion = Move::try_new(
+        pawn,
+        MoveType::PromotionCapture(black_rook, PieceType::Knight),
+        CheckType::None,
+    )
+    .unwrap();
+    assert_eq!(promotion.to_uci(), "a7b8n");
+    let quiet = Move::new(pawn, MoveType::QuietMove(0x70), CheckType::None);
+    assert_eq!(quiet.to_uci(), "a7a8");
+    for (piece, move_type) in [
+        (pawn, MoveType::QuietMove(0x70)),
+        (pawn, MoveType::PromotionQuiet(0x70, PieceType::King)),
+        (knight, MoveType::PromotionQuiet(0x22, PieceType::Queen)),
+        (knight, MoveType::PawnDoublePush(0x21)),
+        (pawn, MoveType::Capture(own_rook)),
+        (knight, MoveType::Castling(CastlingSide::KingSide, own_rook)),
+    ] {
+        assert!(
+            Move::try_new(piece, move_type, CheckType::None).is_err(),
+            "{move_type:?}"
+        );
+    }
+    assert!(Move::try_new(knight, MoveType::QuietMove(0x22), CheckType::None).is_ok());
+}
+
+#[test]
+fn is_legal() {
+    let game = Game::from_fen("4k3/4r3/8/8/8/8/4N3/4K3 w - - 0 1").unwrap();
+    let piece = |pos: u8| Piece::from_code(game.board().inside()[pos as usize], pos);
+    let pinned = Move::new(piece(0x14), MoveType::QuietMove(0x22), CheckType::None);
+    assert!(!game.is_legal(&pinned));
+    let king = Move::new(piece(0x04), MoveType::QuietMove(0x03), CheckType::None);
+    assert!(game.is_legal(&king));
+    // Check annotation doesn't matter
+    let king = Move::new(piece(0x04), MoveType::QuietMove(0x03), CheckType::Direct);
+    assert!(game.is_legal(&king));
+
+    let game = Game::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let pawn = Piece::from_code(game.board().inside()[0x60], 0x60);
+    let promotion = MoveType::PromotionQuiet(0x70, PieceType::Knight);
+    assert!(game.is_legal(&Move::new(pawn, promotion, CheckType::None)));
+    assert!(!game.is_legal(&Move::new(pawn, MoveType::QuietMove(0x70), CheckType::None)));
+}
+
+#[test]
+fn rank_moves() {
+    let game = Game::from_fen("4k3/8/8/3r4/8/8/3Q4/4K3 w - - 0 1").unwrap();
+    let ranked = algorithms::rank_moves(&game, 2);
+    assert_eq!(ranked.len(), game.legal_moves_expanded().len());
+    assert_eq!(ranked[0].0.to_string(), "d2d5");
+    assert!(ranked.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+    assert_eq!(game.to_fen(), "4k3/8/8/3r4/8/8/3Q4/4K3 w - - 0 1");
+}
+
+#[test]
+fn board_scan() {
+    for fen in [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "4k3/8/8/2b5/8/8/5B2/4K3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/4KN2 w - - 0 1",
+    ] {
+        let board = Game::from_fen(fen).unwrap().board().clone();
+        let scan = board.scan();
+        let mut material = [0; 2];
+        for piece in board.iter_pieces().filter(|piece| piece.type_().is_valid()) {
+            material[piece.color() as usize >> 7] += piece.type_().value();
+            assert!(scan.count(piece.color(), piece.type_()) > 0);
         }
-    )*
+        assert_eq!(board.material_count(Color::Black), material[0], "{fen}");
+        assert_eq!(board.material_count(Color::White), material[1], "{fen}");
+        assert_eq!(board.material_balance(), material[1] - material[0], "{fen}");
     }
+    assert_eq!(Board::default().game_phase(), 24);
+    let endgame = Game::from_fen("4k3/8/8/2b5/8/8/5B2/4K3 w - - 0 1").unwrap();
+    assert_eq!(endgame.board().game_phase(), 2);
+    assert!(endgame.board().is_dead_position());
+    // Opponent's bishop could block own king in theory
+    assert!(endgame.board().has_mating_material(Color::White));
+    let knight = Game::from_fen("4k3/8/8/8/8/8/8/4KN2 w - - 0 1").unwrap();
+    assert!(knight.board().is_dead_position());
+    assert!(!knight.board().has_mating_material(Color::White));
+    let same_bishops = Game::from_fen("4k3/8/8/8/8/4B3/8/2B1K3 w - - 0 1").unwrap();
+    assert!(same_bishops.board().is_dead_position());
+    assert!(!same_bishops.board().has_mating_material(Color::White));
+    let bishop_pair = Game::from_fen("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1").unwrap();
+    assert!(bishop_pair.board().has_mating_material(Color::White));
+    let kiwipete =
+        Game::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+            .unwrap();
+    let scan = kiwipete.board().scan();
+    assert_eq!(scan.count(Color::White, PieceType::Pawn), 8);
+    assert_eq!(scan.count(Color::Black, PieceType::Bishop), 2);
+    assert_eq!(scan.bishop_squares, [true, true]);
 }
 
-macro_rules! perft_suit {
-    ($($(#[$attr:meta])* $name:ident: $value:expr)*) => {
-    $(
-        #[test]
-        $(#[$attr])*
-        fn $name() {
-            let (fen_string, depth, expected) = $value;
-            assert!(perf_test(fen_string, depth, expected, true, true), "Results don't match up");
+#[test]
+fn material_evals() {
+    let game = Game::default();
+    assert_eq!(game.material_only_eval(), 0);
+    assert_eq!(evaluate(game.board(), game.current_player()), 0);
+    let game = Game::from_fen("4k3/8/8/8/8/8/PPP5/3QK3 w - - 0 1").unwrap();
+    assert_eq!(game.material_only_eval(), 1200);
+    // 1200 + 1200 * 3 * (8100 - 1200) / (6400 * (3 + 1))
+    assert_eq!(evaluate(game.board(), game.current_player()), 2170);
+    let game = Game::from_fen("4k3/8/8/8/8/8/PPP5/3QK3 b - - 0 1").unwrap();
+    assert_eq!(game.material_only_eval(), -1200);
+    // Black has no pawns, so advantage isn't scaled
+    assert_eq!(evaluate(game.board(), game.current_player()), -1200);
+}
+
+#[test]
+fn evaluate_material() {
+    let board = Board::default();
+    assert_eq!(evaluate(&board, Color::White), 0);
+    assert_eq!(evaluate(&board, Color::Black), 0);
+    let game = Game::from_fen("rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+    let white = evaluate(game.board(), Color::White);
+    assert!(white > 900, "{white}");
+    assert_eq!(evaluate(game.board(), Color::Black), -white);
+}
+
+#[test]
+fn piece_letters() {
+    let letters = [
+        ('p', PieceType::Pawn),
+        ('n', PieceType::Knight),
+        ('b', PieceType::Bishop),
+        ('r', PieceType::Rook),
+        ('q', PieceType::Queen),
+        ('k', PieceType::King),
+    ];
+    for (letter, piece_type) in letters {
+        let upper = letter.to_ascii_uppercase();
+        assert_eq!(PieceType::try_from(letter), Ok(piece_type));
+        assert_eq!(PieceType::try_from(upper), Ok(piece_type));
+        assert_eq!(Color::from_fen_char(letter), Color::Black);
+        assert_eq!(Color::from_fen_char(upper), Color::White);
+    }
+    assert_eq!(PieceType::try_from('x'), Err('x'));
+    assert_eq!(PieceType::try_from('/'), Err('/'));
+}
+
+#[test]
+fn mate_in_one() {
+    let game = Game::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+    let checks: Vec<_> = game
+        .checking_moves()
+        .iter()
+        .map(|_move| _move.to_string())
+        .collect();
+    assert_eq!(checks, vec!["a1a8"]);
+    let mate = game.mate_in_one().expect("Back rank mate should be found");
+    assert_eq!(mate.to_string(), "a1a8");
+    assert!(Game::default().mate_in_one().is_none());
+    // En passant capture giving check counts too
+    let game = Game::from_fen("8/4k3/8/3pP3/8/8/8/K7 w - d6 0 1").unwrap();
+    let checks: Vec<_> = game
+        .checking_moves()
+        .iter()
+        .map(|_move| _move.to_string())
+        .collect();
+    assert_eq!(checks, vec!["e5d6"]);
+}
+
+#[test]
+fn best_move_finds_mate() {
+    for fen in [
+        "4k3/8/4K3/8/8/8/8/6R1 w - - 0 1",
+        // Hanging queen shouldn't distract from mate
+        "4k3/8/4K3/8/8/8/8/q5R1 w - - 0 1",
+        "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1",
+    ] {
+        for depth in 1..=3 {
+            let game = Game::from_fen(fen).unwrap();
+            let _move = best_move(&game, depth).unwrap();
+            let mut next = game.clone();
+            assert_eq!(
+                next.execute(_move.clone()),
+                Some(GameEndState::CheckMate(Color::White)),
+                "{fen} at depth {depth}: {_move}"
+            );
         }
-    )*
+    }
+    let stalemate = Game::from_fen("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1").unwrap();
+    assert!(best_move(&stalemate, 2).is_none());
+}
+
+#[test]
+fn back_rank_weakness() {
+    let game = Game::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+    assert!(game.board().back_rank_weakness(Color::Black));
+    assert!(!game.board().back_rank_weakness(Color::White));
+    let game = Game::from_fen("6k1/5pp1/7p/8/8/8/5PPP/6K1 w - - 0 1").unwrap();
+    assert!(!game.board().back_rank_weakness(Color::Black));
+    assert!(game.board().back_rank_weakness(Color::White));
+}
+
+#[test]
+fn resume_from_sync() {
+    let mut server = Game::default();
+    let mut client = Game::default();
+    // Sync of the same position
+    assert!(!client.resume_from_sync(server.board().clone(), None, server.current_player()));
+    // Sync one move ahead is applied locally
+    play(&mut server, "e2e4");
+    let last_move = server.history().last_move();
+    assert!(!client.resume_from_sync(server.board().clone(), last_move, server.current_player()));
+    assert_eq!(client.to_fen(), server.to_fen());
+    // Diverged position is rebuilt
+    play(&mut client, "e7e5");
+    play(&mut server, "c7c5");
+    let last_move = server.history().last_move();
+    assert!(client.resume_from_sync(server.board().clone(), last_move, server.current_player()));
+    assert_eq!(client.board().inside(), server.board().inside());
+    assert_eq!(client.current_player(), server.current_player());
+}
+
+#[test]
+fn pack_round_trip() {
+    const FENS: [&str; 8] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - ",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - -",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        "r3k3/8/8/8/8/8/8/3K4 b q - 0 1",
+    ];
+    for fen in FENS {
+        let board = Game::from_fen(fen).unwrap().board().clone();
+        let packed = board.pack().unwrap();
+        let unpacked = Board::unpack(&packed);
+        assert_eq!(
+            unpacked.inside(),
+            board.inside(),
+            "Round trip failed for {fen}"
+        );
+        assert_eq!(unpacked.pack().unwrap(), packed);
+    }
+    // Rights lost by moving rook are kept lost
+    let mut game = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    play(&mut game, "h1g1");
+    play(&mut game, "a8b8");
+    play(&mut game, "g1h1");
+    play(&mut game, "b8a8");
+    let unpacked = Board::unpack(&game.board().pack().unwrap());
+    let game = Game::new(unpacked, Color::White, None);
+    assert!(game.to_fen().starts_with("r3k2r/8/8/8/8/8/8/R3K2R w Qk -"));
+    assert_ne!(
+        Board::unpack(&Board::default().pack().unwrap()).pack(),
+        Game::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w Kkq - 0 1")
+            .unwrap()
+            .board()
+            .pack()
+    );
+    // Key has room for 32 pieces only
+    let crowded = Game::from_fen("rnbqkbnr/pppppppp/8/8/8/P7/PPPPPPPP/RNBQKBNR w - - 0 1").unwrap();
+    assert!(crowded.board().pack().is_none());
+}
+
+#[test]
+fn moves_by_piece() {
+    let groups = Game::default().moves_by_piece();
+    assert_eq!(groups.len(), 10);
+    for (piece, moves) in groups.iter() {
+        assert!(matches!(piece.type_(), PieceType::Pawn | PieceType::Knight));
+        assert_eq!(moves.len(), 2);
+        assert!(moves.iter().all(|_move| _move.piece() == piece));
+    }
+    let pawns = groups
+        .iter()
+        .filter(|(piece, _)| piece.type_() == PieceType::Pawn)
+        .count();
+    assert_eq!(pawns, 8);
+}
+
+#[test]
+fn disambiguation() {
+    let find = |game: &Game, _move: &str| {
+        game.legal_moves_expanded()
+            .into_iter()
+            .find(|possible| possible.to_string() == _move)
+            .unwrap()
+    };
+    let game = Game::from_fen("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1").unwrap();
+    assert_eq!(
+        game.disambiguation(&find(&game, "b1d2")),
+        Disambiguation::File
+    );
+    assert_eq!(
+        game.disambiguation(&find(&game, "b1c3")),
+        Disambiguation::None
+    );
+    let game = Game::from_fen("4k3/8/8/N7/8/8/8/N3K3 w - - 0 1").unwrap();
+    assert_eq!(
+        game.disambiguation(&find(&game, "a1b3")),
+        Disambiguation::Rank
+    );
+    let game = Game::from_fen("4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1").unwrap();
+    assert_eq!(
+        game.disambiguation(&find(&game, "a1b2")),
+        Disambiguation::Both
+    );
+}
+
+#[test]
+fn squares_attacked_by() {
+    let board = Board::default();
+    let white = board.squares_attacked_by(Color::White);
+    let attacked: Vec<_> = (0..64u8)
+        .filter(|&i| white[i as usize])
+        .map(|i| pos_to_str(compact_pos(i / 8, i % 8)))
+        .collect();
+    let mut expected = vec!["b1", "c1", "d1", "e1", "f1", "g1"];
+    expected.extend(["a2", "b2", "c2", "d2", "e2", "f2", "g2", "h2"]);
+    expected.extend(["a3", "b3", "c3", "d3", "e3", "f3", "g3", "h3"]);
+    assert_eq!(attacked, expected);
+    assert!(board.square_attacked(0x25, Color::White));
+    assert!(!board.square_attacked(0x35, Color::White));
+    assert!(board.square_attacked(0x55, Color::Black));
+
+    let game =
+        Game::from_fen("r1bqk2r/ppp2ppp/2n2n2/3pp3/1bPP4/2N1PN2/PP3PPP/R1BQKB1R w KQkq - 0 1")
+            .unwrap();
+    for color in [Color::White, Color::Black] {
+        let mask = game.board().squares_attacked_by(color);
+        for i in 0..64u8 {
+            let pos = compact_pos(i / 8, i % 8);
+            assert_eq!(
+                mask[i as usize],
+                game.board().square_attacked(pos, color),
+                "{} by {color}",
+                pos_to_str(pos)
+            );
+        }
+    }
+}
+
+#[test]
+fn pinned_pieces() {
+    let game = Game::from_fen("4k3/4r3/8/8/8/8/4N3/4K3 w - - 0 1").unwrap();
+    let board = game.board();
+    let king = Piece::from_code(board.inside()[0x04], 0x04);
+    let pins = board.pinned_pieces(king, None);
+    assert_eq!(pins.len(), 1);
+    let (pinned, pinner) = pins[0];
+    assert_eq!((pinned.position(), pinner.position()), (0x14, 0x64));
+    assert!(board
+        .is_pinned(0x14)
+        .is_some_and(|pinner| pinner.position() == 0x64));
+    assert!(board.is_pinned(0x64).is_none());
+    assert!(game
+        .legal_moves_expanded()
+        .iter()
+        .all(|_move| _move.piece().position() != 0x14));
+
+    // Both pawns would leave the rank after en passant capture
+    let game = Game::from_fen("4k3/8/8/K2pP2r/8/8/8/8 w - d6 0 1").unwrap();
+    let board = game.board();
+    let king = Piece::from_code(board.inside()[0x40], 0x40);
+    assert!(board.pinned_pieces(king, None).is_empty());
+    let pins = board.pinned_pieces(king, Some(0x43));
+    assert_eq!(pins.len(), 1);
+    assert_eq!((pins[0].0.position(), pins[0].1.position()), (0x44, 0x47));
+    assert!(game.parse_uci("e5d6").is_err());
+}
+
+#[test]
+fn random_positions() {
+    use rand::SeedableRng;
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0x5eed);
+    for _ in 0..100 {
+        let game = Game::random_legal_position(&mut rng, 12);
+        assert!(game.validate().is_ok());
+        let fen = game.to_fen();
+        let restored = Game::from_fen(&fen).unwrap();
+        assert_eq!(restored.to_fen(), fen);
+        assert_eq!(restored.board().inside(), game.board().inside());
+        assert_eq!(restored.current_player(), game.current_player());
+    }
+    assert!(Game::from_fen("4k3/8/8/8/8/8/8/4K2P w - - 0 1")
+        .unwrap()
+        .validate()
+        .is_err());
+    assert!(Game::from_fen("4k2R/8/8/8/8/8/8/4K3 w - - 0 1")
+        .unwrap()
+        .validate()
+        .is_err());
+}
+
+#[test]
+fn play_line() {
+    let mut scratch = Game::default();
+    let line: Vec<_> = ["f2f3", "e7e5", "g2g4", "d8h4"]
+        .into_iter()
+        .map(|_move| {
+            let _move = scratch
+                .legal_moves_expanded()
+                .into_iter()
+                .find(|possible| possible.to_string() == _move)
+                .unwrap();
+            scratch.execute(_move.clone());
+            _move
+        })
+        .collect();
+    let mut game = Game::default();
+    assert!(matches!(game.play_line(&line[..2]), Ok(None)));
+    assert!(matches!(
+        game.play_line(&line[2..]),
+        Ok(Some(GameEndState::CheckMate(Color::Black)))
+    ));
+    let mut game = Game::default();
+    assert_eq!(
+        game.play_line(&line[1..]).unwrap_err(),
+        MoveError::Illegal(0)
+    );
+    let mut game = Game::default();
+    let mut longer = line.clone();
+    longer.push(line[0].clone());
+    assert_eq!(
+        game.play_line(&longer).unwrap_err(),
+        MoveError::GameEnded(4)
+    );
+}
+
+#[test]
+fn material_balance_color_swap() {
+    assert_eq!(Board::default().material_balance(), 0);
+    let board = Game::from_fen("r3k3/pp6/8/8/8/8/PPP5/RN1QK3 w - - 0 1")
+        .unwrap()
+        .board()
+        .clone();
+    assert_eq!(board.material_balance(), 100 + 325 + 900);
+    let swapped = board.swap_colors();
+    assert_eq!(swapped.material_balance(), -board.material_balance());
+    assert_eq!(swapped.swap_colors().inside(), board.inside());
+}
+
+#[test]
+fn uci_moves_round_trip() {
+    let line = [
+        "e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "g8f6", "e1g1", "f6e4", "d2d4", "e5d4", "f1e1",
+        "d7d5", "c4d5", "d8d5", "b1c3", "d5c4", "e1e4", "c8e6", "c1g5", "f8d6",
+    ];
+    let game = Game::from_uci_moves(&line).unwrap();
+    assert_eq!(game.to_uci_moves(), line);
+    assert_eq!(game.fullmove_number(), 11);
+    let restored = Game::from_uci_moves(&game.to_uci_moves()).unwrap();
+    assert_eq!(restored.to_fen(), game.to_fen());
+    assert_eq!(
+        Game::from_uci_moves(&["e2e4", "e2e4"]).unwrap_err(),
+        MoveError::Illegal(1)
+    );
+    let game = Game::from_fen("8/P3k3/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let mut promotions: Vec<_> = game
+        .legal_moves_expanded()
+        .iter()
+        .filter(|_move| _move.piece().type_() == PieceType::Pawn)
+        .map(|_move| _move.to_uci())
+        .collect();
+    promotions.sort();
+    assert_eq!(promotions, ["a7a8b", "a7a8n", "a7a8q", "a7a8r"]);
+    assert!(game.to_uci_moves().is_empty());
+}
+
+#[test]
+fn promotions_to_uci() {
+    let game = Game::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let mut promotions: Vec<_> = game
+        .legal_moves_expanded()
+        .iter()
+        .filter(|_move| _move.piece().position() == 0x60)
+        .map(|_move| (_move.to_uci(), _move.to_string()))
+        .collect();
+    promotions.sort();
+    let expected = ["a7a8b", "a7a8n", "a7a8q", "a7a8r"];
+    assert_eq!(
+        promotions,
+        expected.map(|uci| (uci.to_string(), uci.to_string()))
+    );
+    // Promotion piece isn't chosen yet
+    let pending = game
+        .legal_moves()
+        .into_iter()
+        .find(|_move| _move.piece().position() == 0x60)
+        .unwrap();
+    assert_eq!(pending.to_uci(), "a7a8");
+}
+
+#[test]
+fn parse_uci() {
+    let mut game = Game::from_fen("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+    let push = game.parse_uci("e5e6").unwrap();
+    assert!(matches!(push.move_type(), MoveType::QuietMove(0x54)));
+    let en_passant = game.parse_uci("e5d6").unwrap();
+    assert!(matches!(
+        en_passant.move_type(),
+        MoveType::EnPassantCapture(..)
+    ));
+    let capture = game.parse_uci("a1a8").unwrap();
+    assert!(matches!(capture.move_type(), MoveType::Capture(_)));
+    let castling = game.parse_uci("e1g1").unwrap();
+    assert!(matches!(
+        castling.move_type(),
+        MoveType::Castling(CastlingSide::KingSide, _)
+    ));
+    let underpromotion = game.parse_uci("b7a8n").unwrap();
+    assert!(matches!(
+        underpromotion.move_type(),
+        MoveType::PromotionCapture(_, PieceType::Knight)
+    ));
+    assert_eq!(
+        game.parse_uci("b7b8").unwrap_err(),
+        "Promotion piece missing in UCI move 'b7b8'"
+    );
+    for uci in ["e1e3", "e5e6q", "b7b8k", "b7b8x", "e2", "i1a1", "e1g1 "] {
+        assert!(game.parse_uci(uci).is_err(), "{uci}");
+    }
+    game.execute(castling);
+    // Pawn on b7 covers c8, rook on f1 covers f8
+    assert!(game.parse_uci("e8c8").is_err());
+    assert!(game.parse_uci("e8g8").is_err());
+    assert!(game.parse_uci("e8d8").is_ok());
+}
+
+#[test]
+fn san_notation() {
+    let san = |fen: &str, uci: &str| {
+        let game = Game::from_fen(fen).unwrap();
+        game.to_san(&game.parse_uci(uci).unwrap())
     };
+    let knights = "4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1";
+    assert_eq!(san(knights, "b1d2"), "Nbd2");
+    assert_eq!(san(knights, "f3d2"), "Nfd2");
+    assert_eq!(san(knights, "b1c3"), "Nc3");
+    assert_eq!(san("4k3/8/8/N7/8/8/8/N3K3 w - - 0 1", "a1b3"), "N1b3");
+    assert_eq!(san("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1", "e4d5"), "exd5");
+    assert_eq!(san("k7/4P3/8/8/8/8/8/4K3 w - - 0 1", "e7e8q"), "e8=Q+");
+    assert_eq!(san("k7/4P3/8/8/8/8/8/4K3 w - - 0 1", "e7e8n"), "e8=N");
+    let castling = "4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1";
+    assert_eq!(san(castling, "e1g1"), "O-O");
+    assert_eq!(san(castling, "e1c1"), "O-O-O");
+    assert_eq!(san("6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1", "a1a8"), "Ra8#");
+}
+
+#[test]
+fn parse_san() {
+    let mut game = Game::default();
+    let line = [
+        "e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Bxc6", "dxc6", "O-O", "Bg4", "d3", "Qd6",
+    ];
+    for san in line {
+        let _move = game.parse_san(san).unwrap();
+        assert_eq!(game.to_san(&_move), san);
+        game.execute(_move);
+    }
+    assert!(game.parse_san("Nd2").unwrap_err().contains("ambiguous"));
+    let _move = game.parse_san("Nbd2").unwrap();
+    assert_eq!(_move.to_uci(), "b1d2");
+    game.execute(_move);
+    let _move = game.parse_san("O-O-O!?").unwrap();
+    assert_eq!(_move.to_uci(), "e8c8");
+    for san in ["Ke2", "O-O", "Nbd2", "Zz9", "e", "Qd6d7d8", "exd5"] {
+        assert!(game.parse_san(san).is_err(), "{san}");
+    }
+
+    let game = Game::from_fen("4k3/1P6/8/R7/7Q/8/8/R3K2Q w - - 0 1").unwrap();
+    assert_eq!(game.parse_san("R1a3").unwrap().to_uci(), "a1a3");
+    assert_eq!(game.parse_san("R5a3").unwrap().to_uci(), "a5a3");
+    assert!(game.parse_san("Ra3").is_err());
+    assert_eq!(game.parse_san("Qh4e4").unwrap().to_uci(), "h4e4");
+    assert_eq!(game.parse_san("Q1e4+").unwrap().to_uci(), "h1e4");
+    assert_eq!(game.parse_san("b8=N").unwrap().to_uci(), "b7b8n");
+    assert!(game.parse_san("b8").is_err());
+    assert!(game.parse_san("b8=K").is_err());
+}
+
+#[test]
+fn last_capture() {
+    let mut game = Game::default();
+    assert!(game.last_capture().is_none());
+    play(&mut game, "e2e4");
+    assert!(game.last_capture().is_none());
+    play(&mut game, "d7d5");
+    play(&mut game, "e4d5");
+    assert_eq!(game.last_capture(), Some((PieceType::Pawn, Color::Black)));
+    play(&mut game, "d8d5");
+    assert_eq!(game.last_capture(), Some((PieceType::Pawn, Color::White)));
+    play(&mut game, "b1c3");
+    assert!(game.last_capture().is_none());
+}
+
+#[test]
+fn display_orientation() {
+    // Top left corner
+    assert_eq!(display_cell(0, 0, Color::White), (7, 0));
+    assert_eq!(display_cell(0, 0, Color::Black), (0, 7));
+    // Bottom left corner
+    assert_eq!(display_cell(7, 0, Color::White), (0, 0));
+    assert_eq!(display_cell(7, 0, Color::Black), (7, 7));
+    for color in [Color::White, Color::Black] {
+        for row in 0..8 {
+            for column in 0..8 {
+                let (file, rank) = display_cell(row, column, color);
+                assert_eq!(display_cell(file, rank, color), (row, column));
+            }
+        }
+    }
+}
+
+#[test]
+fn xray_attackers() {
+    let game = Game::from_fen("4k3/8/4n3/8/8/8/4R3/K2B4 w - - 0 1").unwrap();
+    let board = game.board();
+    let xrays = board.xray_attackers(0x74, Color::White);
+    assert_eq!(xrays.len(), 1);
+    assert_eq!(xrays[0].type_(), PieceType::Rook);
+    assert_eq!(xrays[0].position(), 0x14);
+    assert!(board.xray_attackers(0x74, Color::Black).is_empty());
+    // Two blockers are too many, and direct attack isn't an xray
+    let game = Game::from_fen("4k3/4p3/4n3/8/8/8/4R3/K7 w - - 0 1").unwrap();
+    assert!(game.board().xray_attackers(0x74, Color::White).is_empty());
+    let game = Game::from_fen("4k3/8/8/8/8/8/4R3/K7 b - - 0 1").unwrap();
+    assert!(game.board().xray_attackers(0x74, Color::White).is_empty());
+}
+
+#[test]
+fn would_stalemate() {
+    let game = Game::from_fen("k7/8/8/8/8/8/8/1Q5K w - - 0 1").unwrap();
+    let moves = game.legal_moves_expanded();
+    let find = |_move: &str| moves.iter().find(|m| m.to_string() == _move).unwrap();
+    assert!(game.would_stalemate(find("b1b6")));
+    assert!(!game.would_stalemate(find("b1b2")));
+    assert!(!game.would_stalemate(find("b1b7")));
+    let stalemating: Vec<_> = moves
+        .iter()
+        .filter(|_move| game.would_stalemate(_move))
+        .map(|_move| _move.to_string())
+        .collect();
+    assert_eq!(stalemating, vec!["b1b6"]);
+}
+
+#[test]
+fn king_and_center_distance() {
+    let board = Board::default();
+    assert_eq!(board.king_tropism(Color::White, Color::Black), 7);
+    assert_eq!(board.king_tropism(Color::White, Color::White), 0);
+    let game = Game::from_fen("8/8/8/3k4/8/8/8/6K1 w - - 0 1").unwrap();
+    assert_eq!(game.board().king_tropism(Color::Black, Color::White), 7);
+    // White rook on a1 and black king on e8 only
+    let mut packed = [0u8; 24];
+    packed[..8].copy_from_slice(&(1u64 | 1 << 60).to_le_bytes());
+    packed[8] = 0x6c;
+    assert_eq!(
+        Board::unpack(&packed).king_tropism(Color::White, Color::Black),
+        0
+    );
+    let mut closest: Vec<_> = (0..8)
+        .flat_map(|file| (0..8).map(move |rank| compact_pos(file, rank)))
+        .filter(|&pos| center_distance(pos) == 0)
+        .map(pos_to_str)
+        .collect();
+    closest.sort();
+    assert_eq!(closest, vec!["d4", "d5", "e4", "e5"]);
+    assert_eq!(center_distance(0x00), 6);
+    assert_eq!(center_distance(0x77), 6);
+}
+
+#[test]
+fn annotated_history() {
+    let game =
+        Game::from_uci_moves(&["e2e4", "e7e5", "f1c4", "b8c6", "d1h5", "g8f6", "h5f7"]).unwrap();
+    let history = game.annotated_history();
+    let san: Vec<_> = history.iter().map(|info| info.san.as_str()).collect();
+    assert_eq!(san, vec!["e4", "e5", "Bc4", "Nc6", "Qh5", "Nf6", "Qxf7#"]);
+    let mate = history.last().unwrap();
+    assert!(mate.is_mate && mate.is_check && mate.is_capture);
+    assert_eq!(mate.ply, 6);
+    assert!(history[..6]
+        .iter()
+        .all(|info| !info.is_mate && !info.is_capture));
+    // Castling, disambiguation and promotion
+    let mut game = Game::from_fen("r3k3/6P1/8/8/8/8/8/R3K2R w KQq - 0 1").unwrap();
+    play(&mut game, "e1g1");
+    play(&mut game, "e8c8");
+    play(&mut game, "a1a7");
+    play(&mut game, "d8d1");
+    play(&mut game, "f1d1");
+    play(&mut game, "c8b8");
+    play(&mut game, "g7g8q");
+    let san: Vec<_> = game
+        .annotated_history()
+        .into_iter()
+        .map(|info| info.san)
+        .collect();
+    assert_eq!(
+        san,
+        vec!["O-O", "O-O-O", "Ra7", "Rd1", "Rxd1", "Kb8", "g8=Q+"]
+    );
+}
+
+#[test]
+fn repetition_over_whole_game() {
+    let mut game = Game::default();
+    let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+    for _move in shuffle.iter().chain(shuffle.iter()) {
+        assert!(play(&mut game, _move).is_none());
+    }
+    for _move in ["e2e4", "d7d5", "e4d5", "d8d5"] {
+        assert!(play(&mut game, _move).is_none());
+    }
+    // Positions before captures are still counted, each of them was seen twice
+    let occurrences = game.repetition_debug();
+    assert_eq!(
+        occurrences.iter().filter(|(_, count)| *count == 2).count(),
+        4
+    );
+    // Position after the capture occurs for the third time
+    for _move in shuffle.iter().chain(shuffle[..3].iter()) {
+        assert!(play(&mut game, _move).is_none());
+    }
+    assert!(matches!(
+        play(&mut game, "f6g8"),
+        Some(GameEndState::DrawThreefoldRepetition)
+    ));
+    game.undo_last_move().unwrap();
+    assert!(!game.can_claim_draw());
+}
+
+#[test]
+fn repetition_after_double_push() {
+    // No black pawn can take e4 en passant, so position after it repeats
+    let mut game = Game::default();
+    let moves = [
+        "e2e4", "g8f6", "g1f3", "f6g8", "f3g1", "g8f6", "g1f3", "f6g8",
+    ];
+    for _move in moves {
+        assert!(play(&mut game, _move).is_none());
+    }
+    assert_eq!(
+        play(&mut game, "f3g1"),
+        Some(GameEndState::DrawThreefoldRepetition)
+    );
+
+    // Here e5 can take f6 en passant, so position after the push is a new one
+    let mut game = Game::from_fen("4k3/5p2/8/4P3/8/8/8/4K3 b - - 0 1").unwrap();
+    assert!(play(&mut game, "f7f5").is_none());
+    let shuffle = ["e1d1", "e8d8", "d1e1", "d8e8"];
+    for _move in shuffle.iter().chain(shuffle.iter()) {
+        assert!(play(&mut game, _move).is_none());
+    }
+    assert_eq!(game.claimable_draw(), None);
+}
+
+#[test]
+fn hash_history() {
+    let mut game = Game::default();
+    assert!(game.hash_history().is_empty());
+    let line = [
+        "e2e4", "e7e5", "g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6",
+    ];
+    for _move in line {
+        play(&mut game, _move);
+    }
+    let hashes = game.hash_history();
+    assert_eq!(hashes.len(), line.len());
+    assert_eq!(hashes[2], hashes[6]);
+    assert_eq!(hashes[3], hashes[7]);
+    // No white pawn can take e5 en passant, so its target doesn't count
+    assert_eq!(hashes[1], hashes[5]);
+    game.undo_last_move().unwrap();
+    assert_eq!(game.hash_history(), hashes[..7]);
+}
+
+#[test]
+fn repetition_respects_castling_rights() {
+    let mut game = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    let key = |game: &Game| {
+        game.board()
+            .zobrist(Color::White, game.board().castling_rights(), None)
+    };
+    let initial_key = key(&game);
+    for _move in ["e1e2", "e8e7", "e2e1", "e7e8"] {
+        assert!(play(&mut game, _move).is_none());
+    }
+    // Same placement, but rights are lost
+    assert_ne!(key(&game), initial_key);
+    assert!(play(&mut game, "e1e2").is_none());
+    // Black still could castle after the first king move, not anymore
+    assert!(game.repetition_debug().iter().all(|&(_, count)| count == 1));
+}
+
+#[test]
+fn default_game_castling() {
+    // Default game is the standard start, with every castling right
+    let mut game = Game::default();
+    assert_eq!(
+        game.to_fen(),
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+    );
+    for _move in ["e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "g8f6"] {
+        play(&mut game, _move);
+    }
+    let castles: Vec<_> = game
+        .legal_moves()
+        .into_iter()
+        .filter(|_move| matches!(_move.move_type(), MoveType::Castling(..)))
+        .map(|_move| _move.to_uci())
+        .collect();
+    assert_eq!(castles, ["e1g1"]);
+}
+
+#[test]
+fn bot_moves() {
+    let game = Game::from_fen("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let moves = search::bot_moves(&game);
+    assert_eq!(moves.len(), game.legal_moves_expanded().len());
+    let promotions: Vec<_> = moves
+        .iter()
+        .filter_map(|_move| match _move.move_type() {
+            MoveType::PromotionQuiet(_, new_type) => Some((false, *new_type)),
+            MoveType::PromotionCapture(_, new_type) => Some((true, *new_type)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(promotions.len(), 8);
+    for capture in [false, true] {
+        for new_type in [
+            PieceType::Queen,
+            PieceType::Rook,
+            PieceType::Bishop,
+            PieceType::Knight,
+        ] {
+            assert!(promotions.contains(&(capture, new_type)));
+        }
+    }
+    assert!(moves[..4].iter().all(|_move| _move.is_capture()));
+    assert!(moves[4..].iter().all(|_move| !_move.is_capture()));
+}
+
+#[test]
+fn mvv_lva_order() {
+    // Knight can take the queen, pawn can take a pawn and rook can give check
+    let game = Game::from_fen("4k3/8/8/3q4/8/2N1p3/3P4/R3K3 w - - 0 1").unwrap();
+    let mut moves = game.legal_moves_expanded();
+    moves.reverse();
+    order_moves(game.board(), &mut moves);
+    assert_eq!(moves[0].to_uci(), "c3d5");
+    assert_eq!(moves[1].to_uci(), "d2e3");
+    assert!(moves[2..].iter().all(|_move| !_move.is_capture()));
+    assert_eq!(moves[2].to_uci(), "a1a8");
+    assert!(moves[3..]
+        .iter()
+        .all(|_move| _move.check() == CheckType::None));
+    // Least valuable attacker goes first for the same victim
+    let game = Game::from_fen("4k3/8/8/3r4/2P5/8/3Q4/4K3 w - - 0 1").unwrap();
+    let mut moves = game.legal_moves_expanded();
+    order_moves(game.board(), &mut moves);
+    assert_eq!(moves[0].to_uci(), "c4d5");
+    assert_eq!(moves[1].to_uci(), "d2d5");
+}
+
+#[test]
+fn king_of_the_hill() {
+    const FEN: &str = "4k3/8/8/8/8/2K5/8/8 w - - 0 1";
+    let mut game = Game::from_fen(FEN).unwrap();
+    assert!(matches!(
+        play(&mut game, "c3d4"),
+        Some(GameEndState::DrawInsufficientMaterial)
+    ));
+    let mut game = Game::from_fen(FEN)
+        .unwrap()
+        .with_mode(GameMode::KingOfTheHill);
+    assert!(play(&mut game, "c3c4").is_none());
+    assert!(play(&mut game, "e8e7").is_none());
+    assert!(matches!(
+        play(&mut game, "c4d4"),
+        Some(GameEndState::VariantWin(Color::White))
+    ));
+    let mut game = Game::from_fen("8/8/8/8/5K2/8/8/4k3 w - - 0 1")
+        .unwrap()
+        .with_mode(GameMode::KingOfTheHill);
+    assert_eq!(
+        play(&mut game, "f4e4"),
+        Some(GameEndState::VariantWin(Color::White))
+    );
+    // Black king wins as well
+    let mut game = Game::from_fen("8/8/8/8/8/8/1K6/4k3 w - - 0 1")
+        .unwrap()
+        .with_mode(GameMode::KingOfTheHill);
+    for _move in ["b2a1", "e1e2", "a1b1", "e2d3", "b1a1"] {
+        assert!(play(&mut game, _move).is_none());
+    }
+    assert_eq!(
+        play(&mut game, "d3d4"),
+        Some(GameEndState::VariantWin(Color::Black))
+    );
+}
+
+#[test]
+fn three_check() {
+    const FEN: &str = "4k3/8/8/8/8/8/8/R3K3 w - - 0 1";
+    const LINE: [&str; 5] = ["a1a8", "e8e7", "a8a7", "e7e6", "a7a6"];
+    let mut game = Game::from_fen(FEN).unwrap();
+    for _move in LINE {
+        assert!(play(&mut game, _move).is_none());
+    }
+    assert_eq!(game.check_counts(), (3, 0));
+    game.undo_last_move().unwrap();
+    assert_eq!(game.check_counts(), (2, 0));
+    let mut game = Game::from_fen(FEN).unwrap().with_mode(GameMode::ThreeCheck);
+    for _move in &LINE[..4] {
+        assert!(play(&mut game, _move).is_none());
+    }
+    assert_eq!(game.check_counts(), (2, 0));
+    assert_eq!(
+        play(&mut game, LINE[4]),
+        Some(GameEndState::ThreeCheckWin(Color::White))
+    );
+    assert_eq!(pgn::result_token(game.last_end_state()), "1-0");
+}
+
+#[test]
+fn atomic_explosions() {
+    // Rook takes knight next to the black king, which explodes with it
+    let mut game = Game::from_fen("4k3/3n4/8/8/8/8/8/K2R4 w - - 0 1")
+        .unwrap()
+        .with_mode(GameMode::Atomic);
+    assert_eq!(
+        play(&mut game, "d1d7"),
+        Some(GameEndState::VariantWin(Color::White))
+    );
+    assert_eq!(game.to_fen(), "8/8/8/8/8/8/8/K7 b - - 0 1");
+    game.undo_last_move().unwrap();
+    assert_eq!(game.to_fen(), "4k3/3n4/8/8/8/8/8/K2R4 w - - 0 1");
+    // Pawns around survive, every other piece is gone and comes back on undo
+    const FEN: &str = "6k1/8/8/2npr3/2PqN3/3Rb3/1B6/7K w - - 0 1";
+    let mut game = Game::from_fen(FEN).unwrap().with_mode(GameMode::Atomic);
+    assert_eq!(play(&mut game, "b2d4"), None);
+    assert_eq!(game.to_fen(), "6k1/8/8/3p4/2P5/8/8/7K b - - 0 1");
+    game.undo_last_move().unwrap();
+    assert_eq!(game.to_fen(), FEN);
+    assert_eq!(game.board(), Game::from_fen(FEN).unwrap().board());
+    // Neither king captures nor explosions next to own king are allowed
+    let game = Game::from_fen("4k3/3R4/8/8/8/8/3n4/4K3 w - - 0 1")
+        .unwrap()
+        .with_mode(GameMode::Atomic);
+    assert!(!game.legal_moves().iter().any(Move::is_capture));
+    let game = Game::from_fen("4k3/3R4/8/8/8/8/3n4/4K3 w - - 0 1").unwrap();
+    assert_eq!(
+        game.legal_moves()
+            .iter()
+            .filter(|_move| _move.is_capture())
+            .count(),
+        2
+    );
+}
+
+#[test]
+fn atomic_legality() {
+    let atomic = |fen: &str| Game::from_fen(fen).unwrap().with_mode(GameMode::Atomic);
+    let moves = |game: &Game| {
+        let mut moves: Vec<_> = game.legal_moves().iter().map(Move::to_uci).collect();
+        moves.sort();
+        moves
+    };
+    // Exploding the king wins even while in check
+    let mut game = atomic("4r2k/6n1/8/8/8/8/1B6/4K3 w - - 0 1");
+    assert!(moves(&game).contains(&"b2g7".to_string()));
+    assert_eq!(
+        play(&mut game, "b2g7"),
+        Some(GameEndState::VariantWin(Color::White))
+    );
+    // Touching kings can't check each other, so rook doesn't restrict the king
+    let game = atomic("8/8/8/8/8/8/3k4/r2K4 w - - 0 1");
+    assert_eq!(moves(&game), ["d1c1", "d1c2", "d1e1", "d1e2"]);
+    // Kings can't capture, so there is no move at all
+    let game = atomic("7k/8/8/8/8/8/6pp/6nK w - - 0 1");
+    assert!(game.legal_moves().is_empty());
+    assert!(game.first_legal_move().is_none());
+}
+
+#[test]
+fn zobrist_transposition() {
+    let first = Game::from_uci_moves(&["g1f3", "b8c6", "b1c3"]).unwrap();
+    let second = Game::from_uci_moves(&["b1c3", "b8c6", "g1f3"]).unwrap();
+    let key = |game: &Game| {
+        let board = game.board();
+        board.zobrist(game.current_player(), board.castling_rights(), None)
+    };
+    assert_eq!(key(&first), key(&second));
+    assert_ne!(key(&first), key(&Game::default()));
+    let board = first.board();
+    let rights = board.castling_rights();
+    assert_eq!(
+        rights,
+        CastlingRights {
+            white_king_side: true,
+            white_queen_side: true,
+            black_king_side: true,
+            black_queen_side: true,
+        }
+    );
+    assert_ne!(
+        board.zobrist(Color::Black, rights, None),
+        board.zobrist(Color::Black, CastlingRights::default(), None)
+    );
+    assert_ne!(
+        board.zobrist(Color::Black, rights, None),
+        board.zobrist(Color::Black, rights, Some(0x24))
+    );
+}
+
+#[test]
+fn clock_flag_fall() {
+    let clock = Clock::new(Duration::from_secs(60), Duration::from_secs(2));
+    let mut game = Game::default().with_clock(clock);
+    assert!(game.tick(Duration::from_secs(30)).is_none());
+    play(&mut game, "e2e4");
+    assert_eq!(
+        game.clock().unwrap().remaining(Color::White),
+        Duration::from_secs(32)
+    );
+    assert!(game.tick(Duration::from_secs(59)).is_none());
+    play(&mut game, "e7e5");
+    assert!(game.tick(Duration::from_secs(31)).is_none());
+    assert!(matches!(
+        game.tick(Duration::from_secs(1)),
+        Some(GameEndState::TimeForfeit(Color::Black))
+    ));
+    assert!(game.clock().unwrap().is_flagged(Color::White));
+    // Lone king can't win on time
+    let mut game = Game::from_fen("4k3/8/8/8/8/8/8/Q3K3 w - - 0 1")
+        .unwrap()
+        .with_clock(clock);
+    assert!(matches!(
+        game.tick(Duration::from_secs(61)),
+        Some(GameEndState::DrawInsufficientMaterial)
+    ));
+    assert!(Game::default().tick(Duration::from_secs(1000)).is_none());
+    // Undo takes back the increment together with the move
+    let mut game = Game::default().with_clock(clock);
+    play(&mut game, "e2e4");
+    game.undo_last_move().unwrap();
+    assert_eq!(game.clock(), Some(&clock));
+}
+
+#[test]
+fn light_clone_undo_restores_clock() {
+    let clock = Clock::new(Duration::from_secs(60), Duration::from_secs(2));
+    let mut game = Game::default().with_clock(clock);
+    play(&mut game, "g1f3");
+    let mut light = game.light_clone();
+    play(&mut light, "e7e5");
+    light.undo_last_move().unwrap();
+    assert_eq!(light.clock(), game.clock());
+    assert_eq!(light.halfmove_clock(), game.halfmove_clock());
+    light.undo_last_move().unwrap();
+    assert_eq!(light.clock(), Some(&clock));
+    assert_eq!(light.halfmove_clock(), 0);
+}
+
+#[test]
+fn legal_moves_cache() {
+    let to_strings =
+        |moves: &[Move]| -> Vec<String> { moves.iter().map(|_move| _move.to_string()).collect() };
+    let mut game = Game::default();
+    let cached = to_strings(game.legal_moves_cached());
+    assert_eq!(cached, to_strings(&game.legal_moves()));
+    assert_eq!(cached, to_strings(game.legal_moves_cached()));
+    play(&mut game, "e2e4");
+    let cached = to_strings(game.legal_moves_cached());
+    assert_eq!(cached, to_strings(&game.legal_moves()));
+    assert!(cached.contains(&"e7e5".to_string()));
+    game.undo_last_move().unwrap();
+    assert!(to_strings(game.legal_moves_cached()).contains(&"e2e4".to_string()));
+}
+
+#[test]
+fn dead_position() {
+    let dead = |fen: &str| Game::from_fen(fen).unwrap().board().is_dead_position();
+    assert!(dead("4k3/8/8/8/8/8/8/4K3 w - - 0 1"));
+    assert!(dead("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1"));
+    assert!(dead("4k3/8/8/8/8/8/8/1N2K3 w - - 0 1"));
+    assert!(dead("2b1k3/8/8/8/8/8/8/3BK3 w - - 0 1"));
+    assert!(!dead("1n2k3/8/8/8/8/8/8/1N2K3 w - - 0 1"));
+    assert!(!dead("3bk3/8/8/8/8/8/8/3BK3 w - - 0 1"));
+    assert!(!dead("4k3/8/8/8/8/8/8/2B1K1N1 w - - 0 1"));
+    assert!(!dead("4k3/8/8/8/8/8/8/3PK3 w - - 0 1"));
+    assert!(!Board::default().is_dead_position());
+    // Capturing the last pawn ends the game
+    let mut game = Game::from_fen("4k3/8/8/8/8/8/3p4/2B1K3 w - - 0 1").unwrap();
+    assert!(matches!(
+        play(&mut game, "e1d2"),
+        Some(GameEndState::DrawInsufficientMaterial)
+    ));
+}
+
+#[test]
+fn position_snapshot() {
+    let snapshot = Game::default().snapshot();
+    assert_eq!(snapshot.legal_uci.len(), 20);
+    assert!(!snapshot.in_check);
+    assert!(snapshot.outcome.is_none());
+    let game = Game::from_uci_moves(&["f2f3", "e7e5", "g2g4", "d8h4"]).unwrap();
+    let snapshot = game.snapshot();
+    assert_eq!(
+        snapshot.fen,
+        "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3"
+    );
+    assert_eq!(snapshot.side_to_move, Color::White);
+    assert!(snapshot.in_check);
+    assert!(snapshot.legal_uci.is_empty());
+    assert!(matches!(
+        snapshot.outcome,
+        Some(GameEndState::CheckMate(Color::Black))
+    ));
+    // Results not seen on the board come from the recorded end state
+    let mut game = Game::from_fen("4k3/4q3/8/8/8/8/8/4RK2 w - - 0 1")
+        .unwrap()
+        .with_mode(GameMode::Atomic);
+    play(&mut game, "e1e7");
+    assert_eq!(
+        game.snapshot().outcome,
+        Some(GameEndState::VariantWin(Color::White))
+    );
+    let clock = Clock::new(Duration::from_secs(60), Duration::ZERO);
+    let mut game = Game::default().with_clock(clock);
+    game.tick(Duration::from_secs(60));
+    assert_eq!(
+        game.snapshot().outcome,
+        Some(GameEndState::TimeForfeit(Color::Black))
+    );
+    let mut game = Game::default();
+    game.finish(GameEndState::DrawAgreement);
+    assert_eq!(game.snapshot().outcome, Some(GameEndState::DrawAgreement));
+}
+
+#[test]
+fn build_move() {
+    let game = Game::from_fen("r3k3/1P6/8/3pP3/8/8/8/4K2R w Kq d6 0 1").unwrap();
+    let castling = game.build_move(0x04, 0x06, None).unwrap();
+    assert!(matches!(
+        castling.move_type(),
+        MoveType::Castling(CastlingSide::KingSide, _)
+    ));
+    let en_passant = game.build_move(0x44, 0x53, None).unwrap();
+    assert!(matches!(
+        en_passant.move_type(),
+        MoveType::EnPassantCapture(..)
+    ));
+    let promotion = game
+        .build_move(0x61, 0x71, Some(PieceType::Knight))
+        .unwrap();
+    assert!(matches!(
+        promotion.move_type(),
+        MoveType::PromotionQuiet(_, PieceType::Knight)
+    ));
+    let capture = game.build_move(0x61, 0x70, Some(PieceType::Queen)).unwrap();
+    assert!(matches!(
+        capture.move_type(),
+        MoveType::PromotionCapture(_, PieceType::Queen)
+    ));
+    assert!(game.build_move(0x61, 0x71, None).is_err());
+    assert!(game.build_move(0x61, 0x71, Some(PieceType::King)).is_err());
+    assert!(game.build_move(0x04, 0x05, Some(PieceType::Queen)).is_err());
+    assert!(game.build_move(0x04, 0x24, None).is_err());
+}
+
+#[test]
+fn legal_moves_standard_mode() {
+    const FENS: [&str; 4] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - ",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - -",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    ];
+    let to_strings = |moves: Vec<Move>| -> Vec<String> { moves.iter().map(Move::to_uci).collect() };
+    for fen in FENS {
+        let game = Game::from_fen(fen).unwrap();
+        assert_eq!(game.mode(), GameMode::Standard);
+        let expected = to_strings(game.legal_moves_expanded());
+        for mode in [GameMode::KingOfTheHill, GameMode::ThreeCheck] {
+            let game = Game::from_fen(fen).unwrap().with_mode(mode);
+            assert_eq!(to_strings(game.legal_moves_expanded()), expected);
+        }
+    }
+}
+
+#[test]
+fn legal_moves_promotions() {
+    // Quiet promotion on b8 and capture promotion on a8
+    let mut game = Game::from_fen("r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let promotions = |moves: Vec<Move>| moves.into_iter().filter(|_move| _move.promotion()).count();
+    assert_eq!(promotions(game.legal_moves()), 2);
+    assert_eq!(promotions(game.legal_moves_expanded()), 8);
+    assert_eq!(
+        game.legal_moves().len() + 6,
+        game.legal_moves_expanded().len()
+    );
+    // Human picks promotion piece afterwards
+    let mut underpromotion = game
+        .legal_moves()
+        .into_iter()
+        .find(|_move| _move.end_position() == 0x71)
+        .unwrap();
+    underpromotion.set_promotion_type(PieceType::Knight);
+    game.execute(underpromotion);
+    assert_eq!(game.to_fen(), "rN2k3/8/8/8/8/8/8/4K3 b - - 0 1");
+}
+
+#[test]
+fn try_legal_moves() {
+    let game = Game::default();
+    assert_eq!(game.try_legal_moves().unwrap().len(), 20);
+    // White rook on a1 and black king on e8 only
+    let mut packed = [0u8; 24];
+    packed[..8].copy_from_slice(&(1u64 | 1 << 60).to_le_bytes());
+    packed[8] = 0x6c;
+    let kingless = Game::new(Board::unpack(&packed), Color::White, None);
+    assert_eq!(kingless.try_legal_moves().unwrap_err(), "White has no king");
+    // Check isn't recorded in history, generator can't trust it
+    let check = Game::from_fen("4k3/8/8/8/8/8/8/4R1K1 b - - 0 1").unwrap();
+    let check = Game::new(check.board().clone(), Color::Black, None);
+    assert!(check.try_legal_moves().is_err());
+}
+
+#[cfg(feature = "network")]
+#[test]
+fn apply_sync_turns() {
+    use self::online_game::definitions::{apply_sync, ClientTurn};
+    let server = Game::default();
+    let sync = || {
+        (
+            server.board().clone(),
+            server.history().last_move(),
+            server.current_player(),
+        )
+    };
+    let (board, last_move, current) = sync();
+    let (game, turn) = apply_sync(None, board, last_move, current, Color::White);
+    assert_eq!(turn, ClientTurn::Mine);
+    let (board, last_move, current) = sync();
+    let (_, turn) = apply_sync(Some(game), board, last_move, current, Color::Black);
+    assert_eq!(turn, ClientTurn::Opponent);
+    let mut server = server;
+    let mut client = Game::default();
+    play(&mut server, "e2e4");
+    let (game, turn) = apply_sync(
+        Some(client.clone()),
+        server.board().clone(),
+        server.history().last_move(),
+        server.current_player(),
+        Color::Black,
+    );
+    assert_eq!(turn, ClientTurn::Mine);
+    play(&mut client, "e2e4");
+    assert_eq!(game.to_fen(), client.to_fen());
+}
+
+#[test]
+fn claimable_and_automatic_draws() {
+    let mut game = Game::default();
+    let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+    let play_shuffles = |game: &mut Game, count: usize| {
+        for _ in 0..count {
+            for _move in shuffle {
+                play(game, _move);
+            }
+        }
+    };
+    play_shuffles(&mut game, 2);
+    assert!(!game.can_claim_draw());
+    play_shuffles(&mut game, 1);
+    assert_eq!(
+        game.claimable_draw(),
+        Some(GameEndState::DrawThreefoldRepetition)
+    );
+    play_shuffles(&mut game, 1);
+    assert_eq!(game.automatic_draw(), None);
+    play_shuffles(&mut game, 1);
+    assert_eq!(
+        game.automatic_draw(),
+        Some(GameEndState::DrawThreefoldRepetition)
+    );
+    let game = Game::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 100 80").unwrap();
+    assert_eq!(game.claimable_draw(), Some(GameEndState::DrawFiftyMoveRule));
+    assert_eq!(game.automatic_draw(), None);
+    let game = Game::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 150 100").unwrap();
+    assert_eq!(game.automatic_draw(), Some(GameEndState::DrawFiftyMoveRule));
+}
+
+#[cfg(feature = "network")]
+#[test]
+fn online_claimable_draw() {
+    use self::online_game::logic::online_end_state;
+    let mut game = Game::default();
+    let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+    let mut end_state = None;
+    for _move in shuffle.iter().cycle().take(12) {
+        end_state = play(&mut game, _move);
+    }
+    assert_eq!(end_state, Some(GameEndState::DrawThreefoldRepetition));
+    // Player is offered to claim the draw, but game goes on
+    assert_eq!(online_end_state(&game, end_state), None);
+    assert!(game.can_claim_draw());
+    assert_eq!(
+        online_end_state(&game, Some(GameEndState::CheckMate(Color::White))),
+        Some(GameEndState::CheckMate(Color::White))
+    );
+}
+
+/** Room with both players and running game handler, messages to players come out of receivers */
+#[cfg(feature = "network")]
+async fn online_room(
+    time_control: Option<online_game::definitions::TimeControl>,
+) -> (
+    tokio::sync::mpsc::UnboundedSender<(Color, online_game::definitions::ClientMessage)>,
+    [tokio::sync::mpsc::UnboundedReceiver<Result<warp::ws::Message, warp::Error>>; 2],
+) {
+    use self::online_game::definitions::{Client, OnlineGame, Rooms, Uuid};
+    use self::online_game::logic::game_handler;
+    use tokio::sync::mpsc;
+
+    let rooms = Rooms::default();
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let client = |color| {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let client = Client {
+            id: Uuid::new_v4(),
+            sender,
+            game_id: "room".to_string(),
+            color,
+        };
+        (client, receiver)
+    };
+    let (white, white_rcv) = client(Color::White);
+    let (black, black_rcv) = client(Color::Black);
+    let mut room = OnlineGame::new("room".to_string(), sender.clone(), time_control);
+    room.white = Some(white);
+    room.black = Some(black);
+    rooms.write().await.insert("room".to_string(), room);
+    tokio::spawn(game_handler(
+        receiver,
+        rooms.clone(),
+        "room".to_string(),
+        time_control,
+    ));
+    (sender, [black_rcv, white_rcv])
+}
+
+/** Next message sent to the player by the game handler */
+#[cfg(feature = "network")]
+async fn recv_server_message_from(
+    receiver: &mut tokio::sync::mpsc::UnboundedReceiver<Result<warp::ws::Message, warp::Error>>,
+) -> online_game::definitions::ServerMessage {
+    let message = receiver.recv().await.unwrap().unwrap();
+    postcard::from_bytes(message.as_bytes()).unwrap()
+}
+
+/** Messages received by player until `syncs` game state syncs arrive */
+#[cfg(feature = "network")]
+async fn receive_until_syncs(
+    receiver: &mut tokio::sync::mpsc::UnboundedReceiver<Result<warp::ws::Message, warp::Error>>,
+    syncs: usize,
+) -> Vec<online_game::definitions::ServerMessage> {
+    use self::online_game::definitions::ServerMessage;
+
+    let mut messages = Vec::new();
+    let mut received_syncs = 0;
+    while received_syncs < syncs {
+        let message = recv_server_message_from(receiver).await;
+        if matches!(message, ServerMessage::GameStateSync(..)) {
+            received_syncs += 1;
+        }
+        messages.push(message);
+    }
+    messages
+}
+
+#[cfg(feature = "network")]
+#[tokio::test]
+async fn online_resignation() {
+    use self::online_game::definitions::{ClientMessage, ServerMessage};
+
+    let (sender, [_, mut white_rcv]) = online_room(None).await;
+    for message in [
+        (Color::White, ClientMessage::Connected),
+        (Color::Black, ClientMessage::Connected),
+        (Color::Black, ClientMessage::Resigned),
+        // Game is already finished, so it's ignored
+        (Color::White, ClientMessage::Resigned),
+        // Reconnect makes handler answer, so all messages above are processed
+        (Color::White, ClientMessage::Connected),
+    ] {
+        sender.send(message).unwrap();
+    }
+    let finished: Vec<_> = receive_until_syncs(&mut white_rcv, 2)
+        .await
+        .into_iter()
+        .filter_map(|message| match message {
+            ServerMessage::GameFinished(end_state) => Some(end_state),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(finished, vec![GameEndState::Resignation(Color::White)]);
+}
+
+#[cfg(feature = "network")]
+#[tokio::test]
+async fn online_draw_offer() {
+    use self::online_game::definitions::{ClientMessage, ServerMessage};
+
+    let (sender, [mut black_rcv, mut white_rcv]) = online_room(None).await;
+    for message in [
+        (Color::White, ClientMessage::Connected),
+        // Offers are rejected until both players are connected
+        (Color::White, ClientMessage::OfferDraw),
+        (Color::Black, ClientMessage::Connected),
+        (Color::White, ClientMessage::OfferDraw),
+        // Only one offer per side is pending
+        (Color::White, ClientMessage::OfferDraw),
+        // Offering player can't accept own offer
+        (Color::White, ClientMessage::RespondDraw(true)),
+        (Color::Black, ClientMessage::RespondDraw(true)),
+        (Color::White, ClientMessage::Connected),
+        (Color::Black, ClientMessage::Connected),
+    ] {
+        sender.send(message).unwrap();
+    }
+    let black = receive_until_syncs(&mut black_rcv, 2).await;
+    let offers = black
+        .iter()
+        .filter(|message| matches!(message, ServerMessage::DrawOffered))
+        .count();
+    assert_eq!(offers, 1);
+    let white = receive_until_syncs(&mut white_rcv, 2).await;
+    assert!(!white
+        .iter()
+        .any(|message| matches!(message, ServerMessage::DrawOffered)));
+    for messages in [black, white] {
+        let results: Vec<_> = messages
+            .into_iter()
+            .filter_map(|message| match message {
+                ServerMessage::DrawResult(accepted) => Some(Ok(accepted)),
+                ServerMessage::GameFinished(end_state) => Some(Err(end_state)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(results, vec![Ok(true), Err(GameEndState::DrawAgreement)]);
+    }
+}
+
+#[cfg(feature = "network")]
+#[tokio::test]
+async fn online_rematch() {
+    use self::online_game::definitions::{ClientMessage, ServerMessage};
+
+    let (sender, [mut black_rcv, mut white_rcv]) = online_room(None).await;
+    let first_move = Game::default().parse_uci("e2e4").unwrap();
+    for message in [
+        (Color::White, ClientMessage::Connected),
+        (Color::Black, ClientMessage::Connected),
+        // Rematch is only possible after game is finished
+        (Color::White, ClientMessage::RequestRematch),
+        (Color::Black, ClientMessage::Resigned),
+        (Color::White, ClientMessage::RequestRematch),
+        (Color::Black, ClientMessage::RequestRematch),
+        // Player who joined as Black plays White now
+        (Color::Black, ClientMessage::MakeMove(first_move)),
+    ] {
+        sender.send(message).unwrap();
+    }
+    let colors = |messages: Vec<ServerMessage>| -> Vec<_> {
+        messages
+            .into_iter()
+            .filter_map(|message| match message {
+                ServerMessage::GameStateSync(_, last_move, current_player, color) => {
+                    Some((last_move.map(|_move| _move.to_uci()), current_player, color))
+                }
+                _ => None,
+            })
+            .collect()
+    };
+    let e2e4 = Some("e2e4".to_string());
+    assert_eq!(
+        colors(receive_until_syncs(&mut white_rcv, 3).await),
+        vec![
+            (None, Color::White, Color::White),
+            (None, Color::White, Color::Black),
+            (e2e4.clone(), Color::Black, Color::Black),
+        ]
+    );
+    assert_eq!(
+        colors(receive_until_syncs(&mut black_rcv, 3).await),
+        vec![
+            (None, Color::White, Color::Black),
+            (None, Color::White, Color::White),
+            (e2e4, Color::Black, Color::White),
+        ]
+    );
+}
+
+#[cfg(feature = "network")]
+#[tokio::test]
+async fn online_time_forfeit() {
+    use self::online_game::definitions::{ClientMessage, ServerMessage, TimeControl};
+
+    let time_control = TimeControl {
+        base: Duration::from_millis(300),
+        increment: Duration::from_secs(1),
+    };
+    let (sender, [mut black_rcv, mut white_rcv]) = online_room(Some(time_control)).await;
+    let mut game = Game::default();
+    let e2e4 = game.parse_uci("e2e4").unwrap();
+    game.execute(e2e4.clone());
+    let e7e5 = game.parse_uci("e7e5").unwrap();
+    sender
+        .send((Color::White, ClientMessage::Connected))
+        .unwrap();
+    sender
+        .send((Color::Black, ClientMessage::Connected))
+        .unwrap();
+    sender
+        .send((Color::White, ClientMessage::MakeMove(e2e4)))
+        .unwrap();
+    let messages = receive_until_syncs(&mut white_rcv, 2).await;
+    assert!(matches!(
+        messages[..],
+        [
+            ServerMessage::RoomId(_),
+            ServerMessage::GameStateSync(..),
+            ServerMessage::ClockUpdate(300, 300),
+            ServerMessage::OpponentConnected,
+            ServerMessage::GameStateSync(..),
+        ]
+    ));
+    // Increment is given after the move
+    let ServerMessage::ClockUpdate(white_ms, 300) = recv_server_message_from(&mut white_rcv).await
+    else {
+        panic!("Clock update should follow the move");
+    };
+    assert!(white_ms > 1000, "{white_ms}");
+    // Black thinks for longer than they have
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    sender
+        .send((Color::Black, ClientMessage::MakeMove(e7e5)))
+        .unwrap();
+    let mut finished = None;
+    while finished.is_none() {
+        if let ServerMessage::GameFinished(end_state) =
+            recv_server_message_from(&mut black_rcv).await
+        {
+            finished = Some(end_state);
+        }
+    }
+    assert_eq!(finished, Some(GameEndState::TimeForfeit(Color::White)));
+}
+
+/** Websocket client connected to the room through `client_connection` */
+#[cfg(feature = "network")]
+async fn connect_to_room(rooms: &online_game::definitions::Rooms) -> warp::test::WsClient {
+    reconnect_to_room(rooms, None).await.unwrap()
+}
+
+/** Websocket client connected to the room with seat token, `Err` if connection was dropped */
+#[cfg(feature = "network")]
+async fn reconnect_to_room(
+    rooms: &online_game::definitions::Rooms,
+    token: Option<String>,
+) -> Result<warp::test::WsClient, warp::test::WsError> {
+    join_room(rooms, token, None).await
+}
+
+/** Websocket client connected through `client_connection`, time control is
+ * used only if room is created */
+#[cfg(feature = "network")]
+async fn join_room(
+    rooms: &online_game::definitions::Rooms,
+    token: Option<String>,
+    time_control: Option<online_game::definitions::TimeControl>,
+) -> Result<warp::test::WsClient, warp::test::WsError> {
+    use self::online_game::logic::client_connection;
+    use warp::Filter;
+
+    let rooms = rooms.clone();
+    let filter = warp::ws().map(move |ws: warp::ws::Ws| {
+        let rooms = rooms.clone();
+        let token = token.clone();
+        ws.on_upgrade(move |ws| {
+            client_connection(ws, rooms, Some("room".to_string()), time_control, token)
+        })
+    });
+    warp::test::ws().handshake(filter).await
+}
+
+#[cfg(feature = "network")]
+async fn send_client_message(
+    client: &mut warp::test::WsClient,
+    message: online_game::definitions::ClientMessage,
+) {
+    client
+        .send(warp::ws::Message::binary(
+            postcard::to_allocvec(&message).unwrap(),
+        ))
+        .await;
+}
+
+#[cfg(feature = "network")]
+async fn recv_server_message(
+    client: &mut warp::test::WsClient,
+) -> online_game::definitions::ServerMessage {
+    let message = client.recv().await.unwrap();
+    postcard::from_bytes(message.as_bytes()).unwrap()
+}
+
+#[cfg(feature = "network")]
+#[tokio::test]
+async fn online_connected_automatically() {
+    use self::online_game::definitions::{ClientMessage, Rooms, ServerMessage};
+
+    let rooms = Rooms::default();
+    let mut white = connect_to_room(&rooms).await;
+    assert!(matches!(
+        recv_server_message(&mut white).await,
+        ServerMessage::SeatToken(_)
+    ));
+    assert!(matches!(
+        recv_server_message(&mut white).await,
+        ServerMessage::RoomId(_)
+    ));
+    assert!(matches!(
+        recv_server_message(&mut white).await,
+        ServerMessage::GameStateSync(.., Color::White)
+    ));
+    let mut black = connect_to_room(&rooms).await;
+    assert!(matches!(
+        recv_server_message(&mut black).await,
+        ServerMessage::SeatToken(_)
+    ));
+    assert!(matches!(
+        recv_server_message(&mut black).await,
+        ServerMessage::RoomId(_)
+    ));
+    assert!(matches!(
+        recv_server_message(&mut black).await,
+        ServerMessage::GameStateSync(.., Color::Black)
+    ));
+    assert!(matches!(
+        recv_server_message(&mut black).await,
+        ServerMessage::OpponentConnected
+    ));
+    assert!(matches!(
+        recv_server_message(&mut white).await,
+        ServerMessage::OpponentConnected
+    ));
+
+    // Duplicate only resyncs the player, game stays active
+    send_client_message(&mut black, ClientMessage::Connected).await;
+    assert!(matches!(
+        recv_server_message(&mut black).await,
+        ServerMessage::RoomId(_)
+    ));
+    assert!(matches!(
+        recv_server_message(&mut black).await,
+        ServerMessage::GameStateSync(.., Color::Black)
+    ));
+    let e2e4 = Game::default().parse_uci("e2e4").unwrap();
+    send_client_message(&mut white, ClientMessage::MakeMove(e2e4)).await;
+    for player in [&mut white, &mut black] {
+        assert!(matches!(
+            recv_server_message(player).await,
+            ServerMessage::GameStateSync(_, Some(_), Color::Black, _)
+        ));
+    }
+}
+
+#[cfg(feature = "network")]
+#[tokio::test]
+async fn online_spectator() {
+    use self::online_game::definitions::{ClientMessage, Rooms, ServerMessage};
+
+    let rooms = Rooms::default();
+    let mut white = connect_to_room(&rooms).await;
+    for _ in 0..3 {
+        recv_server_message(&mut white).await;
+    }
+    let mut black = connect_to_room(&rooms).await;
+    for _ in 0..4 {
+        recv_server_message(&mut black).await;
+    }
+    assert!(matches!(
+        recv_server_message(&mut white).await,
+        ServerMessage::OpponentConnected
+    ));
+
+    // Third connection to the full room only watches the game
+    let mut spectator = connect_to_room(&rooms).await;
+    assert!(matches!(
+        recv_server_message(&mut spectator).await,
+        ServerMessage::RoomId(_)
+    ));
+    assert!(matches!(
+        recv_server_message(&mut spectator).await,
+        ServerMessage::SpectatorSync(_, None, Color::White)
+    ));
+    assert_eq!(rooms.read().await["room"].spectators.len(), 1);
+
+    let mut game = Game::default();
+    let e2e4 = game.parse_uci("e2e4").unwrap();
+    game.execute(e2e4.clone());
+    send_client_message(&mut white, ClientMessage::MakeMove(e2e4)).await;
+    let ServerMessage::SpectatorSync(_, Some(last_move), Color::Black) =
+        recv_server_message(&mut spectator).await
+    else {
+        panic!("Spectator should see the move");
+    };
+    assert_eq!(last_move.to_uci(), "e2e4");
+
+    // Spectator's move is ignored, so Black still can make the same one
+    let e7e5 = game.parse_uci("e7e5").unwrap();
+    send_client_message(&mut spectator, ClientMessage::MakeMove(e7e5.clone())).await;
+    send_client_message(&mut black, ClientMessage::MakeMove(e7e5)).await;
+    let ServerMessage::SpectatorSync(_, Some(last_move), Color::White) =
+        recv_server_message(&mut spectator).await
+    else {
+        panic!("Spectator should see the move");
+    };
+    assert_eq!(last_move.to_uci(), "e7e5");
+}
+
+#[cfg(feature = "network")]
+#[tokio::test]
+async fn online_third_connection_leaves_players_alone() {
+    use self::online_game::definitions::{ClientMessage, Rooms, ServerMessage};
+
+    let rooms = Rooms::default();
+    let mut white = connect_to_room(&rooms).await;
+    let mut black = connect_to_room(&rooms).await;
+    for _ in 0..4 {
+        recv_server_message(&mut white).await;
+        recv_server_message(&mut black).await;
+    }
+    // Newcomer acting like a player neither takes a seat nor ends the game
+    let mut third = connect_to_room(&rooms).await;
+    send_client_message(&mut third, ClientMessage::Connected).await;
+    send_client_message(&mut third, ClientMessage::Resigned).await;
+    send_client_message(&mut third, ClientMessage::Disconnect).await;
+    drop(third);
+    while !rooms.read().await["room"].spectators.is_empty() {
+        tokio::task::yield_now().await;
+    }
+    let e2e4 = Game::default().parse_uci("e2e4").unwrap();
+    send_client_message(&mut white, ClientMessage::MakeMove(e2e4)).await;
+    for player in [&mut white, &mut black] {
+        let ServerMessage::GameStateSync(_, Some(last_move), Color::Black, _) =
+            recv_server_message(player).await
+        else {
+            panic!("Players should only see the move");
+        };
+        assert_eq!(last_move.to_uci(), "e2e4");
+    }
+}
+
+#[cfg(feature = "network")]
+#[tokio::test]
+async fn online_reconnect_with_seat_token() {
+    use self::online_game::definitions::{ClientMessage, Rooms, ServerMessage};
+
+    let rooms = Rooms::default();
+    let mut white = connect_to_room(&rooms).await;
+    let ServerMessage::SeatToken(white_token) = recv_server_message(&mut white).await else {
+        panic!("Player should get seat token first");
+    };
+    let mut black = connect_to_room(&rooms).await;
+    let ServerMessage::SeatToken(black_token) = recv_server_message(&mut black).await else {
+        panic!("Player should get seat token first");
+    };
+    for _ in 0..3 {
+        recv_server_message(&mut white).await;
+        recv_server_message(&mut black).await;
+    }
+    let e2e4 = Game::default().parse_uci("e2e4").unwrap();
+    send_client_message(&mut white, ClientMessage::MakeMove(e2e4)).await;
+    recv_server_message(&mut white).await;
+    recv_server_message(&mut black).await;
+
+    // Seat of connected player can't be taken over
+    let mut intruder = reconnect_to_room(&rooms, Some(black_token.clone()))
+        .await
+        .unwrap();
+    assert!(intruder.recv_closed().await.is_ok());
+    assert!(reconnect_to_room(&rooms, Some("forged".to_string()))
+        .await
+        .unwrap()
+        .recv_closed()
+        .await
+        .is_ok());
+
+    send_client_message(&mut white, ClientMessage::Disconnect).await;
+    drop(white);
+    assert!(matches!(
+        recv_server_message(&mut black).await,
+        ServerMessage::OpponentDisconnected
+    ));
+    let mut white = reconnect_to_room(&rooms, Some(white_token.clone()))
+        .await
+        .unwrap();
+    assert!(matches!(
+        recv_server_message(&mut white).await,
+        ServerMessage::SeatToken(token) if token == white_token
+    ));
+    assert!(matches!(
+        recv_server_message(&mut white).await,
+        ServerMessage::RoomId(_)
+    ));
+    let ServerMessage::GameStateSync(_, Some(last_move), Color::Black, Color::White) =
+        recv_server_message(&mut white).await
+    else {
+        panic!("Reconnected player should get the game back as White");
+    };
+    assert_eq!(last_move.to_uci(), "e2e4");
+    assert!(matches!(
+        recv_server_message(&mut white).await,
+        ServerMessage::OpponentConnected
+    ));
+    assert!(matches!(
+        recv_server_message(&mut black).await,
+        ServerMessage::OpponentConnected
+    ));
+}
+
+#[cfg(feature = "network")]
+#[tokio::test]
+async fn online_free_seat_needs_token_after_start() {
+    use self::online_game::definitions::{ClientMessage, Rooms, ServerMessage};
+
+    let rooms = Rooms::default();
+    let mut white = connect_to_room(&rooms).await;
+    let ServerMessage::SeatToken(white_token) = recv_server_message(&mut white).await else {
+        panic!("Player should get seat token first");
+    };
+    let mut black = connect_to_room(&rooms).await;
+    for _ in 0..3 {
+        recv_server_message(&mut white).await;
+    }
+    for _ in 0..4 {
+        recv_server_message(&mut black).await;
+    }
+    send_client_message(&mut white, ClientMessage::Disconnect).await;
+    drop(white);
+    assert!(matches!(
+        recv_server_message(&mut black).await,
+        ServerMessage::OpponentDisconnected
+    ));
+
+    // Newcomer without token gets neither the free seat nor its token
+    let mut third = connect_to_room(&rooms).await;
+    assert!(matches!(
+        recv_server_message(&mut third).await,
+        ServerMessage::RoomId(_)
+    ));
+    assert!(matches!(
+        recv_server_message(&mut third).await,
+        ServerMessage::SpectatorSync(..)
+    ));
+    assert!(rooms.read().await["room"].white.is_none());
+
+    let mut white = reconnect_to_room(&rooms, Some(white_token.clone()))
+        .await
+        .unwrap();
+    assert!(matches!(
+        recv_server_message(&mut white).await,
+        ServerMessage::SeatToken(token) if token == white_token
+    ));
+}
+
+#[cfg(feature = "network")]
+#[tokio::test]
+async fn online_disconnect_charges_clock() {
+    use self::online_game::definitions::{ClientMessage, Rooms, ServerMessage, TimeControl};
+
+    let time_control = TimeControl {
+        base: Duration::from_secs(10),
+        increment: Duration::ZERO,
+    };
+    let rooms = Rooms::default();
+    let mut white = join_room(&rooms, None, Some(time_control)).await.unwrap();
+    let ServerMessage::SeatToken(white_token) = recv_server_message(&mut white).await else {
+        panic!("Player should get seat token first");
+    };
+    let mut black = connect_to_room(&rooms).await;
+    for _ in 0..4 {
+        recv_server_message(&mut white).await;
+    }
+    for _ in 0..5 {
+        recv_server_message(&mut black).await;
+    }
+    // White thinks before leaving, clock doesn't run while they are away
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    send_client_message(&mut white, ClientMessage::Disconnect).await;
+    drop(white);
+    assert!(matches!(
+        recv_server_message(&mut black).await,
+        ServerMessage::OpponentDisconnected
+    ));
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let mut white = reconnect_to_room(&rooms, Some(white_token)).await.unwrap();
+    for _ in 0..3 {
+        recv_server_message(&mut white).await;
+    }
+    let ServerMessage::ClockUpdate(white_ms, 10_000) = recv_server_message(&mut white).await else {
+        panic!("Reconnected player should get the clock");
+    };
+    assert!((9_200..=9_700).contains(&white_ms), "{white_ms}");
+}
+
+#[test]
+fn chess960_positions() {
+    assert_eq!(Game::chess960(518).to_fen(), Game::default().to_fen());
+    let mut back_ranks = std::collections::HashSet::new();
+    for id in 0..960 {
+        let game = Game::chess960(id);
+        let fen = game.to_fen();
+        let back_rank = fen.split('/').next().unwrap().to_string();
+        assert!(fen.contains(&format!("/{}", back_rank.to_ascii_uppercase())));
+        let bishops: Vec<_> = back_rank.match_indices('b').map(|(i, _)| i).collect();
+        assert_eq!(bishops.len(), 2);
+        assert_ne!(bishops[0] % 2, bishops[1] % 2, "{back_rank}");
+        let king = back_rank.find('k').unwrap();
+        assert!(back_rank.find('r').unwrap() < king && king < back_rank.rfind('r').unwrap());
+        back_ranks.insert(back_rank);
+    }
+    assert_eq!(back_ranks.len(), 960);
+    assert!(back_ranks.contains("bbqnnrkr"));
+}
+
+#[test]
+fn chess960_castling() {
+    let mut game = Game::from_fen("4k3/8/8/8/8/8/8/1R4KR w KQ - 0 1").unwrap();
+    let castlings: Vec<_> = game.castling_moves().iter().map(Move::to_uci).collect();
+    assert_eq!(castlings, ["g1h1", "g1b1"]);
+    // King already stands on its destination, only the rook moves
+    play(&mut game, "g1h1");
+    assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/8/1R3RK1 b - - 1 1");
+    game.undo_last_move().unwrap();
+    assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/8/1R4KR w KQ - 0 1");
+    play(&mut game, "g1b1");
+    assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/8/2KR3R b - - 1 1");
+    // King step to g1 isn't castling, classical castling keeps classical notation
+    let game = Game::from_fen("4k3/8/8/8/8/8/8/R4K1R w KQ - 0 1").unwrap();
+    assert_eq!(
+        game.parse_uci("f1g1").unwrap().move_type(),
+        &MoveType::QuietMove(0x06)
+    );
+    assert!(matches!(
+        game.parse_uci("f1h1").unwrap().move_type(),
+        MoveType::Castling(CastlingSide::KingSide, _)
+    ));
+    let game = Game::from_fen("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+    assert_eq!(game.castling_moves()[0].to_uci(), "e1g1");
+    // Castling rook hides the attacker of king's destination
+    let game = Game::from_fen("4k3/8/8/8/8/8/8/rR4K1 w Q - 0 1").unwrap();
+    assert!(game.castling_moves().is_empty());
+    // Chess960 rights survive FEN and packing
+    let game = Game::chess960(0);
+    assert!(game.to_fen().ends_with(" w KQkq - 0 1"));
+    let board = game.board().clone();
+    assert_eq!(
+        Board::unpack(&board.pack().unwrap()).inside(),
+        board.inside()
+    );
+}
+
+#[test]
+fn en_passant_target() {
+    let mut game = Game::default();
+    assert_eq!(game.en_passant_target(), None);
+    play(&mut game, "e2e4");
+    assert_eq!(game.en_passant_target(), Some(0x24));
+    play(&mut game, "g8f6");
+    assert_eq!(game.en_passant_target(), None);
+    play(&mut game, "d2d3");
+    assert_eq!(game.en_passant_target(), None);
+    play(&mut game, "c7c5");
+    assert_eq!(game.en_passant_target().map(pos_to_str), Some("c6"));
+}
+
+#[test]
+fn castling_rights_query() {
+    let mut game = Game::default();
+    assert_eq!(game.castling_rights(Color::White), (true, true));
+    assert_eq!(game.castling_rights(Color::Black), (true, true));
+    for _move in ["a2a4", "h7h5", "a1a3"] {
+        play(&mut game, _move);
+    }
+    assert_eq!(game.castling_rights(Color::White), (true, false));
+    play(&mut game, "h8h6");
+    assert_eq!(game.castling_rights(Color::Black), (false, true));
+    // Rook coming back doesn't restore the right
+    play(&mut game, "a3a1");
+    assert_eq!(game.castling_rights(Color::White), (true, false));
+    assert_eq!(
+        game.board().castling_rights(),
+        CastlingRights {
+            white_king_side: true,
+            white_queen_side: false,
+            black_king_side: false,
+            black_queen_side: true,
+        }
+    );
+    let game = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w Kq - 0 1").unwrap();
+    assert_eq!(game.castling_rights(Color::White), (true, false));
+    assert_eq!(game.castling_rights(Color::Black), (false, true));
+}
+
+#[test]
+fn game_serde_round_trip() {
+    let mut game = Game::default().with_clock(Clock::new(Duration::from_secs(300), Duration::ZERO));
+    for _move in [
+        "e2e4", "c7c5", "g1f3", "d7d6", "d2d4", "c5d4", "f3d4", "g8f6",
+    ] {
+        play(&mut game, _move);
+    }
+    let bytes = postcard::to_allocvec(&game).unwrap();
+    let mut restored: Game = postcard::from_bytes(&bytes).unwrap();
+    assert_eq!(restored.to_fen(), game.to_fen());
+    assert_eq!(restored.to_uci_moves(), game.to_uci_moves());
+    assert_eq!(restored.hash_history(), game.hash_history());
+    assert_eq!(restored.clock(), game.clock());
+    assert_eq!(
+        restored.legal_moves_cached().len(),
+        game.legal_moves_cached().len()
+    );
+    // Restored game keeps playing and undoing like the original
+    restored.undo_last_move().unwrap();
+    game.undo_last_move().unwrap();
+    assert_eq!(restored.to_fen(), game.to_fen());
+}
+
+#[test]
+fn board_equality() {
+    assert_eq!(Board::default(), Board::default());
+    assert_ne!(Board::default(), Board::new());
+    let mut game = Game::default();
+    play(&mut game, "g1f3");
+    assert_ne!(game.board(), &Board::default());
+    // Knight is back, but it's flagged as moved now
+    for _move in ["g8f6", "f3g1", "f6g8"] {
+        play(&mut game, _move);
+    }
+    assert_eq!(
+        game.to_fen(),
+        Game::default().to_fen().replace(" 0 1", " 4 3")
+    );
+    assert_ne!(game.board(), &Board::default());
+    game.undo_last_move().unwrap();
+    game.undo_last_move().unwrap();
+    game.undo_last_move().unwrap();
+    game.undo_last_move().unwrap();
+    assert_eq!(game.board(), &Board::default());
+}
+
+#[test]
+fn piece_counts() {
+    let board = Board::default();
+    let counts = board.piece_counts();
+    assert_eq!(counts[Color::White as usize >> 7], [8, 2, 2, 2, 1, 1]);
+    assert_eq!(counts[Color::Black as usize >> 7], [8, 2, 2, 2, 1, 1]);
+    assert_eq!(board.material_count(Color::White), 4050);
+    let game = Game::from_fen("4k3/8/8/8/8/8/1P6/RQ2K3 w - - 0 1").unwrap();
+    let counts = game.board().piece_counts();
+    assert_eq!(counts[Color::White as usize >> 7], [1, 0, 0, 1, 1, 1]);
+    assert_eq!(counts[Color::Black as usize >> 7], [0, 0, 0, 0, 0, 1]);
+    assert_eq!(game.board().material_count(Color::White), 1500);
+    assert_eq!(game.board().material_count(Color::Black), 0);
+}
+
+#[test]
+fn end_state_is_cached() {
+    let mut game = Game::from_fen("7k/5K2/8/6Q1/8/8/8/8 w - - 0 1").unwrap();
+    assert_eq!(game.last_end_state(), None);
+    assert_eq!(play(&mut game, "g5g6"), Some(GameEndState::DrawStalemate));
+    assert_eq!(game.last_end_state(), Some(GameEndState::DrawStalemate));
+    assert_eq!(game.light_clone().last_end_state(), game.last_end_state());
+    game.undo_last_move().unwrap();
+    assert_eq!(game.last_end_state(), None);
+    play(&mut game, "g5g7");
+    assert_eq!(
+        game.last_end_state(),
+        Some(GameEndState::CheckMate(Color::White))
+    );
+    game.undo_last_move().unwrap();
+    play(&mut game, "g5g4");
+    assert_eq!(game.last_end_state(), None);
+}
+
+#[test]
+fn apply_notation_lines() {
+    const RUY_LOPEZ: &str = "r1bqkb1r/1ppp1ppp/p1n2n2/4p3/B3P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 3 5";
+    let mut game = Game::default();
+    let line = [
+        "e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "a7a6", "b5a4", "g8f6", "e1g1",
+    ];
+    assert_eq!(game.apply_uci_line(&line), Ok(None));
+    assert_eq!(game.to_fen(), RUY_LOPEZ);
+    let mut game = Game::default();
+    let line = ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6", "O-O"];
+    assert_eq!(game.apply_san_line(&line), Ok(None));
+    assert_eq!(game.to_fen(), RUY_LOPEZ);
+    // Moves before the failing one stay played
+    let mut game = Game::default();
+    assert_eq!(
+        game.apply_san_line(&["e4", "e5", "Ke3"]),
+        Err(MoveError::Illegal(2))
+    );
+    assert_eq!(game.to_uci_moves(), ["e2e4", "e7e5"]);
+    let mut game = Game::default();
+    assert_eq!(
+        game.apply_uci_line(&["f2f3", "e7e5", "g2g4", "d8h4", "a2a3"]),
+        Err(MoveError::GameEnded(4))
+    );
+    let mut game = Game::default();
+    assert_eq!(
+        game.apply_san_line(&["f3", "e5", "g4", "Qh4#"]),
+        Ok(Some(GameEndState::CheckMate(Color::Black)))
+    );
+}
+
+#[test]
+fn first_legal_move() {
+    let game = Game::default();
+    let first = game.first_legal_move().expect("Start position has moves");
+    assert!(game
+        .legal_moves()
+        .iter()
+        .any(|_move| _move.to_uci() == first.to_uci()));
+    let stalemate = Game::from_fen("7k/5K2/6Q1/8/8/8/8/8 b - - 0 1").unwrap();
+    assert!(stalemate.first_legal_move().is_none());
+    // Only the king can escape, the rest of pieces have to be skipped
+    let check = Game::from_fen("4k3/8/8/8/8/8/PPP5/RN2K2r w - - 0 1").unwrap();
+    let first = check.first_legal_move().expect("King can step away");
+    assert_eq!(first.piece().type_(), PieceType::King);
+}
+
+#[test]
+fn perft_counts_stalemates() {
+    // Qg6 and Qf5 stalemate, Qh4-h6, Qg7 and Qg8 mate
+    let game = Game::from_fen("7k/5K2/8/6Q1/8/8/8/8 w - - 0 1").unwrap();
+    let result = perft(&game, 2);
+    assert_eq!(result.stalemates(), 2);
+    assert_eq!(result.checkmates(), 5);
+    assert_eq!(perft(&game, 1).all(), game.legal_moves_expanded().len());
+}
+
+#[test]
+fn perft_divide_sums_to_perft() {
+    let game = Game::default();
+    let divide = perft_divide(&game, 3);
+    assert_eq!(divide.len(), 20);
+    assert!(divide.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    assert!(divide.contains(&("e2e4".to_string(), 600)));
+    let total: usize = divide.iter().map(|(_, nodes)| nodes).sum();
+    assert_eq!(total, perft(&game, 3).all());
+    assert_eq!(total, 8902);
+    assert_eq!(perft_divide(&game, 1).len(), 20);
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn perft_parallel_matches_serial() {
+    use self::utils::perft_parallel;
+
+    let game = Game::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - ")
+        .unwrap();
+    assert_eq!(perft_parallel(&game, 4), perft(&game, 4).all());
+    assert_eq!(perft_parallel(&game, 4), 4_085_603);
+    assert_eq!(perft_parallel(&game, 1), 48);
+}
+
+macro_rules! perf_tests {
+    ($fen_string:literal $($(#[$attr:meta])* $name:ident: $value:expr)*) => {
+    $(
+        #[test]
+        $(#[$attr])*
+        fn $name() {
+            let fen_string = $fen_string;
+            let (expected, depth) = $value;
+            assert!(perf_test(fen_string, depth, expected, true, true), "Results don't match up");
+        }
+    )*
+    }
+}
+
+macro_rules! perft_suit {
+    ($($(#[$attr:meta])* $name:ident: $value:expr)*) => {
+    $(
+        #[test]
+        $(#[$attr])*
+        fn $name() {
+            let (fen_string, depth, expected) = $value;
+            assert!(perf_test(fen_string, depth, expected, true, true), "Results don't match up");
+        }
+    )*
+    };
+}
+
+perf_tests! {
+    "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9"
+    perft_chess960_1_3: (12189, 3)
+    perft_chess960_1_4: (326_672, 4)
+}
+
+perf_tests! {
+    "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w KQkq - 1 9"
+    perft_chess960_2_3: (18002, 3)
+    perft_chess960_2_4: (667_366, 4)
 }
 
 perf_tests! {
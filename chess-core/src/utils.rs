@@ -13,6 +13,7 @@ pub struct PERFResult {
     discovery_checks: usize,
     double_checks: usize,
     checkmates: usize,
+    stalemates: usize,
 }
 
 impl PERFResult {
@@ -27,8 +28,53 @@ impl PERFResult {
             discovery_checks: self.discovery_checks + other.discovery_checks,
             double_checks: self.double_checks + other.double_checks,
             checkmates: self.checkmates + other.checkmates,
+            stalemates: self.stalemates + other.stalemates,
         }
     }
+
+    /** Number of leaf nodes. */
+    pub fn all(&self) -> usize {
+        self.all
+    }
+
+    pub fn captures(&self) -> usize {
+        self.captures
+    }
+
+    pub fn en_passants(&self) -> usize {
+        self.en_passaunt
+    }
+
+    pub fn castles(&self) -> usize {
+        self.castles
+    }
+
+    pub fn promotions(&self) -> usize {
+        self.promotions
+    }
+
+    /** All checks, including discovered and double ones. */
+    pub fn checks(&self) -> usize {
+        self.checks
+    }
+
+    pub fn discovery_checks(&self) -> usize {
+        self.discovery_checks
+    }
+
+    pub fn double_checks(&self) -> usize {
+        self.double_checks
+    }
+
+    /** Checkmates reached before the last ply, they end the branch early. */
+    pub fn checkmates(&self) -> usize {
+        self.checkmates
+    }
+
+    /** Stalemates reached before the last ply, they end the branch early. */
+    pub fn stalemates(&self) -> usize {
+        self.stalemates
+    }
 }
 
 impl AddAssign for PERFResult {
@@ -42,6 +88,7 @@ impl AddAssign for PERFResult {
         self.discovery_checks += rhs.discovery_checks;
         self.double_checks += rhs.double_checks;
         self.checkmates += rhs.checkmates;
+        self.stalemates += rhs.stalemates;
     }
 }
 
@@ -49,7 +96,7 @@ impl Display for PERFResult {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "{} - cp: {:<4} ep: {:<4} cs: {:<4} pr: {:<4} Ch: {:<4} dCh: {:<4} Ch2: {:<4} CM: {:4}",
+            "{} - cp: {:<4} ep: {:<4} cs: {:<4} pr: {:<4} Ch: {:<4} dCh: {:<4} Ch2: {:<4} CM: {:<4} SM: {:4}",
             self.all,
             self.captures,
             self.en_passaunt,
@@ -58,7 +105,8 @@ impl Display for PERFResult {
             self.checks,
             self.discovery_checks,
             self.double_checks,
-            self.checkmates
+            self.checkmates,
+            self.stalemates
         )
     }
 }
@@ -100,8 +148,17 @@ fn count_perf_result(moves: Vec<Move>) -> PERFResult {
     result
 }
 
+fn end_state_result(end_state: GameEndState) -> PERFResult {
+    let is_mate = matches!(end_state, GameEndState::CheckMate(_));
+    PERFResult {
+        checkmates: is_mate as usize,
+        stalemates: !is_mate as usize,
+        ..Default::default()
+    }
+}
+
 fn perf_test_step_undo(game: &mut Game, depth: usize) -> PERFResult {
-    let possible_moves = game.get_possible_moves(true);
+    let possible_moves = game.legal_moves_expanded();
     if depth == 0 {
         PERFResult {
             all: 1,
@@ -112,16 +169,13 @@ fn perf_test_step_undo(game: &mut Game, depth: usize) -> PERFResult {
     } else {
         let mut result = PERFResult::default();
         for _move in possible_moves.into_iter() {
-            result += if let Some(end_state) = game.execute(_move) {
+            // Draws by rule don't end move generation
+            result += if let Some(
+                end_state @ (GameEndState::CheckMate(_) | GameEndState::DrawStalemate),
+            ) = game.execute(_move)
+            {
                 game.undo_last_move().expect("Failed to undo valid move");
-                PERFResult {
-                    checkmates: if matches!(end_state, GameEndState::CheckMate(_)) {
-                        1
-                    } else {
-                        0
-                    },
-                    ..Default::default()
-                }
+                end_state_result(end_state)
             } else {
                 let result = perf_test_step_undo(game, depth - 1);
                 game.undo_last_move().expect("Failed to undo valid move");
@@ -133,7 +187,7 @@ fn perf_test_step_undo(game: &mut Game, depth: usize) -> PERFResult {
 }
 
 fn perf_test_step_copy(game: Game, depth: usize) -> PERFResult {
-    let possible_moves = game.get_possible_moves(true);
+    let possible_moves = game.legal_moves_expanded();
     if depth == 0 {
         PERFResult {
             all: 1,
@@ -146,15 +200,11 @@ fn perf_test_step_copy(game: Game, depth: usize) -> PERFResult {
             .into_iter()
             .map(|_move| {
                 let mut game = game.light_clone();
-                if let Some(end_state) = game.execute(_move) {
-                    PERFResult {
-                        checkmates: if matches!(end_state, GameEndState::CheckMate(_)) {
-                            1
-                        } else {
-                            0
-                        },
-                        ..Default::default()
-                    }
+                if let Some(
+                    end_state @ (GameEndState::CheckMate(_) | GameEndState::DrawStalemate),
+                ) = game.execute(_move)
+                {
+                    end_state_result(end_state)
                 } else {
                     perf_test_step_copy(game, depth - 1)
                 }
@@ -164,6 +214,56 @@ fn perf_test_step_copy(game: Game, depth: usize) -> PERFResult {
     }
 }
 
+/** Runs perft from `game` position and returns collected statistics. */
+pub fn perft(game: &Game, depth: usize) -> PERFResult {
+    perf_test_step_copy(game.light_clone(), depth)
+}
+
+/** Leaf node count at given depth, subtrees of root moves are counted in parallel */
+#[cfg(feature = "parallel")]
+pub fn perft_parallel(game: &Game, depth: usize) -> usize {
+    use rayon::prelude::*;
+
+    if depth <= 1 {
+        return perf_test_step_copy(game.light_clone(), depth).all;
+    }
+    game.legal_moves_expanded()
+        .into_par_iter()
+        .map(|_move| {
+            let mut game = game.light_clone();
+            match game.execute(_move) {
+                Some(GameEndState::CheckMate(_) | GameEndState::DrawStalemate) => 0,
+                _ => perf_test_step_copy(game, depth - 1).all,
+            }
+        })
+        .sum()
+}
+
+/** Node count of every root move at given depth, sorted by move in UCI.
+ *
+ * Output matches Stockfish's `go perft`, so miscounted subtree can be found by diffing. */
+pub fn perft_divide(game: &Game, depth: usize) -> Vec<(String, usize)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+    let mut divide: Vec<_> = game
+        .legal_moves_expanded()
+        .into_iter()
+        .map(|_move| {
+            let uci = _move.to_uci();
+            let mut game = game.light_clone();
+            let nodes = match game.execute(_move) {
+                _ if depth == 1 => 1,
+                Some(GameEndState::CheckMate(_) | GameEndState::DrawStalemate) => 0,
+                _ => perf_test_step_copy(game, depth - 1).all,
+            };
+            (uci, nodes)
+        })
+        .collect();
+    divide.sort();
+    divide
+}
+
 pub fn perf_test(
     fen_string: &str,
     depth: usize,
@@ -176,7 +276,7 @@ pub fn perf_test(
     println!(" - setup: | {fen_string} | depth: {depth} detailed: {detailed}");
     if detailed {
         let mut total: PERFResult = Default::default();
-        for _move in game.get_possible_moves(true) {
+        for _move in game.legal_moves_expanded() {
             let mut temp_game = game.clone();
             temp_game.execute(_move.clone());
             let result = if undo {
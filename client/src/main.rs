@@ -175,7 +175,7 @@ impl App {
                                     _ => {
                                         let moves: Vec<_> = self
                                             .game
-                                            .get_possible_moves(false)
+                                            .legal_moves()
                                             .into_iter()
                                             .filter(|_move| _move.piece() == &piece)
                                             .collect();
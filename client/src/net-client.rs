@@ -1,10 +1,11 @@
-use std::str::FromStr;
+use std::{str::FromStr, time::Duration};
 
 use chess_core::{
     Color, PieceType,
     core::{
         definitions::ImplicitMove,
         engine::{Game, GameEndState, Move, Piece},
+        game::display_cell,
         utils::unpack_pos,
     },
 };
@@ -27,6 +28,8 @@ struct App {
     selected_cell: Option<(usize, usize)>,
     moves: Option<Vec<Move>>,
     promotion_type: PieceType,
+    /// Time left for White and Black, only in timed games
+    clock: Option<(Duration, Duration)>,
 }
 
 fn main() -> Result<(), eframe::Error> {
@@ -56,6 +59,7 @@ fn main() -> Result<(), eframe::Error> {
                 selected_cell: None,
                 moves: None,
                 promotion_type: PieceType::Queen,
+                clock: None,
             })
         }),
     );
@@ -80,7 +84,10 @@ impl eframe::App for App {
                 state => {
                     let game = current_game.expect(&format!("Game is None in '{state:?}'"));
                     ui.horizontal(|ui| {
-                        let move_to_exec = self.grid(ui, &game);
+                        // Spectator can only look at the board
+                        let move_to_exec = self
+                            .grid(ui, &game)
+                            .filter(|_| !matches!(state, ClientState::Spectating));
                         self.control_panel(ui, &game);
                         if let Some(mut _move) = move_to_exec {
                             if _move.promotion() {
@@ -117,6 +124,18 @@ impl App {
             OnlineClientOutput::IncorrectInput => {
                 error!("Incorrect user action!");
             }
+            OnlineClientOutput::DrawClaimable => {
+                info!("Draw can be claimed!");
+            }
+            OnlineClientOutput::DrawOffered => {
+                info!("Opponent offers a draw!");
+            }
+            OnlineClientOutput::DrawResult(accepted) => {
+                info!("Draw offer was accepted: {}", accepted);
+            }
+            OnlineClientOutput::ClockUpdate(white, black) => {
+                self.clock = Some((white, black));
+            }
         }
     }
 
@@ -124,7 +143,9 @@ impl App {
         ui.vertical(|ui| {
             ui.heading("Test chess");
             ui.label(format!("You are: {}", {
-                if self.client.player_color() == Color::White {
+                if matches!(self.last_state, ClientState::Spectating) {
+                    "Spectator"
+                } else if self.client.player_color() == Color::White {
                     "White"
                 } else {
                     "Black"
@@ -137,6 +158,12 @@ impl App {
                     "Black"
                 }
             }));
+            if let Some((white, black)) = self.clock {
+                let format_time =
+                    |time: Duration| format!("{}:{:02}", time.as_secs() / 60, time.as_secs() % 60);
+                ui.label(format!("White: {}", format_time(white)));
+                ui.label(format!("Black: {}", format_time(black)));
+            }
             ui.label(format!(
                 "Is checked: {:?}",
                 game.history().last_move().map(|_move| _move.check())
@@ -165,6 +192,7 @@ impl App {
 
     fn grid(&mut self, ui: &mut egui::Ui, game: &Game) -> Option<Move> {
         let board = game.board();
+        let point_of_view = self.client.player_color();
         let mut move_to_exec = None;
         egui::Grid::new("main_grid")
             .striped(true)
@@ -172,8 +200,9 @@ impl App {
             .max_col_width(self.cell_size)
             .min_row_height(self.cell_size)
             .show(ui, |ui| {
-                for file in (0..8).rev() {
-                    for rank in 0..8 {
+                for row in 0..8 {
+                    for column in 0..8 {
+                        let (file, rank) = display_cell(row, column, point_of_view);
                         let piece = board.get(file, rank);
                         let btn = if let Some(source) = piece_image(&piece) {
                             egui::Button::image(source)
@@ -234,9 +263,10 @@ impl App {
                                     PieceType::Invalid | PieceType::EmptySquare => None,
                                     _ => {
                                         let moves: Vec<_> = game
-                                            .get_possible_moves(false)
-                                            .into_iter()
+                                            .legal_moves_cached()
+                                            .iter()
                                             .filter(|_move| _move.piece() == &piece)
+                                            .cloned()
                                             .collect();
                                         // dbg!(&moves);
                                         self.chosen_figure = if !moves.is_empty() {
@@ -1,9 +1,12 @@
-use std::sync::{
-    Arc,
-    mpsc::{Receiver, Sender},
+use std::{
+    sync::{
+        Arc,
+        mpsc::{Receiver, Sender},
+    },
+    time::Duration,
 };
 
-use futures::{SinkExt, StreamExt, future};
+use futures::{StreamExt, future};
 use log::{error, info};
 use postcard::{from_bytes, to_allocvec};
 use tokio::sync::{
@@ -15,11 +18,13 @@ use url::Url;
 
 use chess_core::{
     Color,
-    core::engine::{Game, Move},
+    core::engine::{Board, Game, Move},
     online_game::definitions::ParsingMessageError,
 };
 
-use chess_core::online_game::definitions::{ClientMessage, GameId, ServerMessage};
+use chess_core::online_game::definitions::{
+    ClientMessage, ClientTurn, GameId, ServerMessage, apply_sync,
+};
 
 struct ClientMessageWrapper(ClientMessage);
 struct ServerMessageWrapper(ServerMessage);
@@ -63,6 +68,8 @@ pub enum ClientState {
     GameTurnValidation,
     GameEnemyTurn,
     GameFinished,
+    /// Joined full room, game is only watched
+    Spectating,
 }
 
 #[derive(Default, Clone)]
@@ -71,18 +78,29 @@ struct OnlineClientData {
     pub game: Arc<Mutex<Option<Game>>>,
     pub game_id: Arc<Mutex<String>>,
     pub color: Arc<Mutex<Color>>,
+    pub seat_token: Arc<Mutex<Option<String>>>,
 }
 
 pub enum OnlineClientInput {
     Disconnect,
     Move(Move),
     Resign,
+    ClaimDraw,
+    OfferDraw,
+    RespondDraw(bool),
+    RequestRematch,
 }
 
 pub enum OnlineClientOutput {
     ReceivedGameId,
     StateChanged(ClientState),
     IncorrectInput,
+    DrawClaimable,
+    /// Opponent offers a draw, answer with `OnlineClient::respond_draw`
+    DrawOffered,
+    DrawResult(bool),
+    /// Time left for White and Black in timed games
+    ClockUpdate(Duration, Duration),
 }
 
 pub struct OnlineClient {
@@ -98,24 +116,65 @@ impl Into<OnlineClientOutput> for ClientState {
     }
 }
 
+impl From<ClientTurn> for ClientState {
+    fn from(value: ClientTurn) -> Self {
+        match value {
+            ClientTurn::Mine => ClientState::GameMyTurn,
+            ClientTurn::Opponent => ClientState::GameEnemyTurn,
+        }
+    }
+}
+
+async fn sync_game(
+    data: &OnlineClientData,
+    board: Board,
+    last_move: Option<Move>,
+    current_player: Color,
+    client_color: Color,
+) -> ClientTurn {
+    let mut game = data.game.lock().await;
+    let (synced, turn) = apply_sync(game.take(), board, last_move, current_player, client_color);
+    *game = Some(synced);
+    turn
+}
+
 async fn handle_server_message(
     data: &OnlineClientData,
     message: ServerMessage,
 ) -> Result<Option<OnlineClientOutput>, ()> {
+    // Clock is updated in any state, without changing it
+    if let ServerMessage::ClockUpdate(white_ms, black_ms) = message {
+        return Ok(Some(OnlineClientOutput::ClockUpdate(
+            Duration::from_millis(white_ms),
+            Duration::from_millis(black_ms),
+        )));
+    }
     // TODO: is it okay to do so?
     let state = { *data.state.lock().await };
     match state {
         ClientState::Unconnected => match message {
             ServerMessage::GameStateSync(board, last_move, current_player, client_color) => {
-                *data.game.lock().await = Some(Game::new(board, current_player, last_move));
+                *data.game.lock().await = None;
+                sync_game(data, board, last_move, current_player, client_color).await;
                 *data.color.lock().await = client_color;
                 *data.state.lock().await = ClientState::WaitingOpponent;
                 Ok(Some(ClientState::WaitingOpponent.into()))
             }
+            ServerMessage::SpectatorSync(board, last_move, current_player) => {
+                *data.game.lock().await = None;
+                // Spectators watch the game from White's side
+                sync_game(data, board, last_move, current_player, Color::White).await;
+                *data.state.lock().await = ClientState::Spectating;
+                Ok(Some(ClientState::Spectating.into()))
+            }
             ServerMessage::RoomId(game_id) => {
                 *data.game_id.lock().await = game_id;
                 Ok(Some(OnlineClientOutput::ReceivedGameId))
             }
+            ServerMessage::SeatToken(token) => {
+                *data.seat_token.lock().await = Some(token);
+                Ok(None)
+            }
             _ => Err(()),
         },
         ClientState::WaitingOpponent => match message {
@@ -137,6 +196,15 @@ async fn handle_server_message(
                 *data.state.lock().await = ClientState::WaitingOpponent;
                 Ok(Some(ClientState::WaitingOpponent.into()))
             }
+            ServerMessage::DrawClaimable => Ok(Some(OnlineClientOutput::DrawClaimable)),
+            ServerMessage::DrawOffered => Ok(Some(OnlineClientOutput::DrawOffered)),
+            ServerMessage::DrawResult(accepted) => {
+                Ok(Some(OnlineClientOutput::DrawResult(accepted)))
+            }
+            ServerMessage::GameFinished(_) => {
+                *data.state.lock().await = ClientState::GameFinished;
+                Ok(Some(ClientState::GameFinished.into()))
+            }
             _ => Err(()),
         },
         ClientState::GameTurnValidation => match message {
@@ -145,15 +213,21 @@ async fn handle_server_message(
                 Ok(Some(ClientState::WaitingOpponent.into()))
             }
             ServerMessage::GameStateSync(board, last_move, current_player, client_color) => {
-                *data.game.lock().await = Some(Game::new(board, current_player, last_move));
-                let new_state = if current_player == client_color {
-                    ClientState::GameMyTurn
-                } else {
-                    ClientState::GameEnemyTurn
-                };
+                let new_state: ClientState =
+                    sync_game(data, board, last_move, current_player, client_color)
+                        .await
+                        .into();
                 *data.state.lock().await = new_state;
                 Ok(Some(new_state.into()))
             }
+            ServerMessage::DrawOffered => Ok(Some(OnlineClientOutput::DrawOffered)),
+            ServerMessage::DrawResult(accepted) => {
+                Ok(Some(OnlineClientOutput::DrawResult(accepted)))
+            }
+            ServerMessage::GameFinished(_) => {
+                *data.state.lock().await = ClientState::GameFinished;
+                Ok(Some(ClientState::GameFinished.into()))
+            }
             _ => Err(()),
         },
         ClientState::GameEnemyTurn => match message {
@@ -161,10 +235,22 @@ async fn handle_server_message(
                 *data.state.lock().await = ClientState::WaitingOpponent;
                 Ok(Some(ClientState::WaitingOpponent.into()))
             }
-            ServerMessage::GameStateSync(board, last_move, current_player, _) => {
-                *data.game.lock().await = Some(Game::new(board, current_player, last_move));
-                *data.state.lock().await = ClientState::GameMyTurn;
-                Ok(Some(ClientState::GameMyTurn.into()))
+            ServerMessage::GameStateSync(board, last_move, current_player, client_color) => {
+                let new_state: ClientState =
+                    sync_game(data, board, last_move, current_player, client_color)
+                        .await
+                        .into();
+                *data.state.lock().await = new_state;
+                Ok(Some(new_state.into()))
+            }
+            ServerMessage::DrawClaimable => Ok(Some(OnlineClientOutput::DrawClaimable)),
+            ServerMessage::DrawOffered => Ok(Some(OnlineClientOutput::DrawOffered)),
+            ServerMessage::DrawResult(accepted) => {
+                Ok(Some(OnlineClientOutput::DrawResult(accepted)))
+            }
+            ServerMessage::GameFinished(_) => {
+                *data.state.lock().await = ClientState::GameFinished;
+                Ok(Some(ClientState::GameFinished.into()))
             }
             _ => Err(()),
         },
@@ -174,9 +260,35 @@ async fn handle_server_message(
                     // TODO: Potential to restart game!
                     Ok(None)
                 }
+                // Rematch started, colors are swapped
+                ServerMessage::GameStateSync(board, last_move, current_player, client_color) => {
+                    *data.game.lock().await = None;
+                    let new_state: ClientState =
+                        sync_game(data, board, last_move, current_player, client_color)
+                            .await
+                            .into();
+                    *data.color.lock().await = client_color;
+                    *data.state.lock().await = new_state;
+                    Ok(Some(new_state.into()))
+                }
                 _ => Err(()),
             }
         }
+        ClientState::Spectating => match message {
+            ServerMessage::SpectatorSync(board, last_move, current_player) => {
+                // Rematch starts from scratch
+                if last_move.is_none() {
+                    *data.game.lock().await = None;
+                }
+                sync_game(data, board, last_move, current_player, Color::White).await;
+                Ok(None)
+            }
+            // Spectators can't act on these, game goes on being shown as is
+            ServerMessage::DrawClaimable
+            | ServerMessage::DrawResult(_)
+            | ServerMessage::GameFinished(_) => Ok(None),
+            _ => Err(()),
+        },
     }
 }
 
@@ -218,6 +330,28 @@ async fn handle_client_input(
             }
             _ => Ok((Some(OnlineClientOutput::IncorrectInput), None)),
         },
+        OnlineClientInput::ClaimDraw => match state {
+            ClientState::GameMyTurn | ClientState::GameEnemyTurn => {
+                Ok((None, Some(ClientMessage::ClaimDraw)))
+            }
+            _ => Ok((Some(OnlineClientOutput::IncorrectInput), None)),
+        },
+        OnlineClientInput::RequestRematch => match state {
+            ClientState::GameFinished => Ok((None, Some(ClientMessage::RequestRematch))),
+            _ => Ok((Some(OnlineClientOutput::IncorrectInput), None)),
+        },
+        OnlineClientInput::OfferDraw => match state {
+            ClientState::GameMyTurn | ClientState::GameEnemyTurn => {
+                Ok((None, Some(ClientMessage::OfferDraw)))
+            }
+            _ => Ok((Some(OnlineClientOutput::IncorrectInput), None)),
+        },
+        OnlineClientInput::RespondDraw(accepted) => match state {
+            ClientState::GameMyTurn
+            | ClientState::GameTurnValidation
+            | ClientState::GameEnemyTurn => Ok((None, Some(ClientMessage::RespondDraw(accepted)))),
+            _ => Ok((Some(OnlineClientOutput::IncorrectInput), None)),
+        },
     }
 }
 
@@ -253,6 +387,13 @@ impl OnlineClient {
         self.data.game_id.blocking_lock().clone()
     }
 
+    /// Wait and receive token to rejoin the room with `?token=` after a drop!
+    ///  - Blocking function!
+    ///  - `None` for spectators
+    pub fn seat_token(&self) -> Option<String> {
+        self.data.seat_token.blocking_lock().clone()
+    }
+
     /// Wait and receive current online client color!
     ///  - Blocking function!
     pub fn player_color(&self) -> Color {
@@ -279,6 +420,22 @@ impl OnlineClient {
         let _ = self.input.send(OnlineClientInput::Resign);
     }
 
+    pub fn claim_draw(&self) {
+        let _ = self.input.send(OnlineClientInput::ClaimDraw);
+    }
+
+    pub fn request_rematch(&self) {
+        let _ = self.input.send(OnlineClientInput::RequestRematch);
+    }
+
+    pub fn offer_draw(&self) {
+        let _ = self.input.send(OnlineClientInput::OfferDraw);
+    }
+
+    pub fn respond_draw(&self, accepted: bool) {
+        let _ = self.input.send(OnlineClientInput::RespondDraw(accepted));
+    }
+
     pub fn disconnect(&self) {
         let _ = self.input.send(OnlineClientInput::Disconnect);
     }
@@ -295,10 +452,10 @@ impl OnlineClient {
         output_tx: Sender<OnlineClientOutput>,
     ) -> Result<(), tungstenite::Error> {
         let (websocket, _) = tokio_tungstenite::connect_async(url).await?;
-        let (mut write, read) = websocket.split();
+        let (write, read) = websocket.split();
         let input = UnboundedReceiverStream::new(input_rx);
 
-        let _ = write.send(ClientMessage::Connected.wrap().into()).await;
+        // Server announces connection on its own, `Connected` would only resync
         let data_cp = data.clone();
         let output_tx_cp = output_tx.clone();
         let client_handling = tokio::spawn(